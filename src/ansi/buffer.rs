@@ -5,9 +5,14 @@
 //!
 //! Source: [muesli/reflow/ansi/buffer.go](https://github.com/muesli/reflow/blob/00a9f5c6902562434539e11d2c8f8d3dae851318/ansi/buffer.go)
 
+use std::ops::{Bound, RangeBounds};
+
 use bytes::{Bytes, BytesMut};
 
-use crate::{Error, Result};
+use crate::{
+    iter::{Token, Tokens},
+    Error, Result,
+};
 
 /// A thin wrapper around [`bytes::BytesMut`] that is able to determine visual string size.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -84,51 +89,18 @@ impl Buffer {
     }
 
     fn visible_len_ascii(&self) -> Result<usize> {
-        let mut count = 0usize;
-        let mut toggle = false;
-
-        for b in &self.0 {
-            let ch = *b as char;
-            // println!(
-            //     "Byte '{:x}' Char '{}' Count = {} Toggle = {}",
-            //     b,
-            //     ch,
-            //     count,
-            //     if toggle { "ON" } else { "OFF" }
-            // );
-            if ch == super::MARKER {
-                toggle = true;
-            } else if toggle {
-                if super::is_terminator(ch) {
-                    toggle = false;
-                }
-            } else {
-                count += char::len_utf8(ch);
-            }
-        }
+        let count = Tokens::from_bytes(&self.0)?
+            .map(|token| match token {
+                Token::Text(text) => text.len(),
+                Token::Escape { .. } => 0,
+            })
+            .sum();
 
         Ok(count)
     }
 
     fn visible_len_unicode(&self) -> Result<usize> {
-        let mut count = 0usize;
-        let mut toggle = false;
-
-        let string = self.to_str()?;
-
-        for ch in string.chars() {
-            if ch == super::MARKER {
-                toggle = true;
-            } else if toggle {
-                if super::is_terminator(ch) {
-                    toggle = false;
-                }
-            } else {
-                count += char::len_utf8(ch);
-            }
-        }
-
-        Ok(count)
+        Ok(super::visible_width(self.to_str()?))
     }
 
     /// Adds the given string slice to the internal buffer.
@@ -164,6 +136,20 @@ impl Buffer {
         self.0.extend(std::iter::once(c as u8));
     }
 
+    /// Clears the internal buffer, removing all of its contents but keeping the allocated capacity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use reflors::ansi::buffer::Buffer;
+    /// let mut buffer = Buffer::from("Hello World!");
+    /// buffer.clear();
+    /// assert!(buffer.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
     /// Mutably borrow the internal [`BytesMut`] buffer, mostly useful in case I
     /// forgot to provide a wrapper to access to any useful methods on that type.
     ///
@@ -403,22 +389,152 @@ impl Buffer {
         self.0.capacity()
     }
 
-    /// Creates a new [`String`] from the current value of the buffer, truncating the
-    /// last `len` **visible** characters.
+    /// Truncates the buffer to its first `len` **visible** columns, and returns the result as a new
+    /// [`String`]. If the buffer isn't wider than `len` it is left as it is.
+    ///
+    /// The styles still active at the cut are reset, and a wide character straddling it is
+    /// replaced with a space (see [`Buffer::slice_visible`]).
     ///
     /// ## Errors
-    /// - If the current buffer is not valid utf8.
+    /// - `crate::Error::Utf8` if the current buffer is not valid utf8.
     ///
-    /// ## Panics
-    /// - Because it is not written.
+    /// # Examples
+    /// ```
+    /// # use reflors::ansi::buffer::Buffer;
+    /// let mut buffer = Buffer::from("\x1b[1mHello\x1b[0m World!");
+    /// assert_eq!(buffer.truncate_visible(3).unwrap(), "\x1b[1mHel\x1b[0m");
+    /// assert_eq!(buffer.visible_len().unwrap(), 3);
+    /// ```
     pub fn truncate_visible(&mut self, len: usize) -> Result<String> {
-        let current = self.visible_len()?;
-        if current <= len {
-            todo!();
+        if self.visible_len()? <= len {
+            return self.to_string();
         }
 
-        let to_remove = current - len;
-        todo!("ansi::Buffer::truncate_visible not implemented!");
+        let truncated = self.slice_visible(..len)?;
+        self.0 = BytesMut::from(truncated.as_bytes());
+        Ok(truncated)
+    }
+
+    /// Creates a new [`String`] covering the given range of **visible** columns of the buffer.
+    ///
+    /// The slice starts with the escape sequences needed to reproduce the styles active at its
+    /// first column, keeps the escape sequences within the range and ends with a reset if any
+    /// style is still active. A wide character straddling either end of the range is replaced with
+    /// spaces, so the slice is exactly as wide as the range (unless the buffer ends before it).
+    /// Columns are counted the same way as [`Buffer::visible_len`], starting over at each `\n`, so
+    /// every line of multi-line content is sliced on its own.
+    ///
+    /// ## Errors
+    /// - `crate::Error::Utf8` if the current buffer is not valid utf8.
+    ///
+    /// # Examples
+    /// ```
+    /// # use reflors::ansi::buffer::Buffer;
+    /// let buffer = Buffer::from("plain \x1b[31mred \x1b[1mbold\x1b[0m");
+    /// assert_eq!(buffer.slice_visible(8..12).unwrap(), "\x1b[31md \x1b[1mbo\x1b[0m");
+    /// assert_eq!(buffer.slice_visible(..3).unwrap(), "pla");
+    /// assert_eq!(buffer.slice_visible(12..).unwrap(), "\x1b[31m\x1b[1mld\x1b[0m");
+    /// ```
+    pub fn slice_visible(&self, range: impl RangeBounds<usize>) -> Result<String> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => usize::MAX,
+        };
+        if start >= end {
+            return Ok(String::new());
+        }
+
+        let mut output = String::new();
+        // the styles set since the last reset
+        let mut active = String::new();
+        // the styles set since the last reset in the output
+        let mut written = String::new();
+        let mut column = 0;
+        // whether a character of the current line has been kept
+        let mut started = false;
+        let mut last_kept = false;
+
+        for token in Tokens::new(self.to_str()?) {
+            let text = match token {
+                Token::Text(text) => text,
+                Token::Escape { raw, .. } => {
+                    let update = |styles: &mut String| {
+                        if token.is_reset() {
+                            styles.clear();
+                        } else if token.is_sgr() {
+                            styles.push_str(raw);
+                        }
+                    };
+                    update(&mut active);
+                    // a reset past the end still closes the styles written before it
+                    if started && (column < end || (token.is_reset() && !written.is_empty())) {
+                        update(&mut written);
+                        output.push_str(raw);
+                    }
+                    continue;
+                }
+            };
+
+            let mut chars = text.chars().peekable();
+            while let Some(ch) = chars.next() {
+                // every line is sliced on its own, just like `visible_width` measures them
+                if ch == '\n' || (ch == '\r' && chars.peek() == Some(&'\n')) {
+                    output.push(ch);
+                    if ch == '\n' {
+                        column = 0;
+                        started = false;
+                        last_kept = false;
+                    }
+                    continue;
+                }
+
+                let width = super::char_width_at(ch, column);
+                if width == 0 {
+                    if last_kept {
+                        output.push(ch);
+                    }
+                    continue;
+                }
+
+                let col = column;
+                column += width;
+                if col >= end || column <= start {
+                    last_kept = false;
+                    continue;
+                }
+                if !started {
+                    started = true;
+                    if let Some(missing) = active.strip_prefix(written.as_str()) {
+                        output.push_str(missing);
+                    } else {
+                        output.push_str("\x1b[0m");
+                        output.push_str(&active);
+                    }
+                    written.clone_from(&active);
+                }
+
+                last_kept = col >= start && column <= end;
+                if last_kept {
+                    output.push(ch);
+                } else {
+                    // a wide character straddling the start or the end of the range
+                    let columns = column.min(end) - col.max(start);
+                    output.extend(std::iter::repeat_n(' ', columns));
+                }
+            }
+        }
+
+        if !written.is_empty() {
+            output.push_str("\x1b[0m");
+        }
+
+        Ok(output)
     }
 }
 
@@ -624,27 +740,132 @@ mod tests {
         let b2: Buffer = "\u{1b}[1;4;38;2;255;255m🤔\u{1b}[0m".into();
         assert_eq!(char::len_utf8('🤔'), 4, "🤔 should be 4 bytes");
         assert_eq!(b1.len(), 4, "Buffer length should be 4");
-        assert_eq!(b1.visible_len(), Ok(4), "Buffer visible length should be 4");
+        assert_eq!(b1.visible_len(), Ok(2), "Buffer visible length should be 2");
         assert_eq!(b2.len(), 27, "Buffer length should be 27");
-        assert_eq!(b2.visible_len(), Ok(4), "Buffer visible length should be 4");
+        assert_eq!(b2.visible_len(), Ok(2), "Buffer visible length should be 2");
         assert_eq!(b1.visible_len(), b2.visible_len());
 
         let b1: Buffer = "東京".into();
         let b2: Buffer = "\u{1b}[1;4;38;2;255;255m東京\u{1b}[0m".into();
         assert_eq!(b1.len(), 6, "Buffer length should be 6");
-        assert_eq!(b1.visible_len(), Ok(6), "Buffer visible length should be 6");
+        assert_eq!(b1.visible_len(), Ok(4), "Buffer visible length should be 4");
         assert_eq!(b2.len(), 29, "Buffer length should be 29");
-        assert_eq!(b2.visible_len(), Ok(6), "Buffer visible length should be 6");
+        assert_eq!(b2.visible_len(), Ok(4), "Buffer visible length should be 4");
         assert_eq!(b1.visible_len(), b2.visible_len());
     }
 
     #[test]
     fn single() {
         let b2: Buffer = "\u{1b}[1;4;38;2;255;255m🤔\u{1b}[0m".into();
-        assert_eq!(b2.visible_len(), Ok(4), "Buffer visible length should be 4");
+        assert_eq!(b2.visible_len(), Ok(2), "Buffer visible length should be 2");
         assert!(!b2.is_ascii(), "Buffer should be ASCII");
     }
 
+    #[test]
+    fn zero_width() {
+        let b: Buffer = "\u{1b}[1msoft\u{ad}ware\u{200b}!\u{1b}[0m".into();
+        assert_eq!(b.visible_len(), Ok(9), "Buffer visible length should be 9");
+        let b: Buffer = "\u{2764}\u{fe0f}\u{200d}x".into();
+        assert_eq!(b.visible_len(), Ok(2), "Buffer visible length should be 2");
+    }
+
+    #[test]
+    fn tabs() {
+        let b: Buffer = "\tx".into();
+        assert_eq!(b.visible_len(), Ok(9), "Buffer visible length should be 9");
+        assert_eq!(b.slice_visible(4..).unwrap(), "    x");
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        let b: Buffer = link.into();
+        assert_eq!(b.visible_len(), Ok(4), "Buffer visible length should be 4");
+        assert_eq!(b.slice_visible(1..3).unwrap(), "in");
+    }
+
+    #[test]
+    fn slice_visible() {
+        let b: Buffer = "\u{1b}[1mbold\u{1b}[0m and \u{1b}[31mred\u{1b}[0m".into();
+        assert_eq!(b.slice_visible(..), Ok(b.to_string().unwrap()));
+        assert_eq!(
+            b.slice_visible(..4),
+            Ok("\u{1b}[1mbold\u{1b}[0m".to_string())
+        );
+        assert_eq!(
+            b.slice_visible(2..6),
+            Ok("\u{1b}[1mld\u{1b}[0m a".to_string())
+        );
+        assert_eq!(b.slice_visible(5..=7), Ok("and".to_string()));
+        assert_eq!(
+            b.slice_visible(10..),
+            Ok("\u{1b}[31med\u{1b}[0m".to_string())
+        );
+        assert_eq!(b.slice_visible(3..3), Ok(String::new()));
+        assert_eq!(b.slice_visible(20..30), Ok(String::new()));
+
+        // bounds at the very end of the range of `usize` don't overflow
+        assert_eq!(
+            b.slice_visible(1..=usize::MAX),
+            Ok("\u{1b}[1mold\u{1b}[0m and \u{1b}[31mred\u{1b}[0m".to_string())
+        );
+        assert_eq!(
+            b.slice_visible((Bound::Excluded(usize::MAX), Bound::Unbounded)),
+            Ok(String::new())
+        );
+
+        // styles carry over from before the start, until they are reset
+        let b: Buffer = "\u{1b}[1m\u{1b}[4mab\u{1b}[0mc\u{1b}[31md".into();
+        assert_eq!(
+            b.slice_visible(1..2),
+            Ok("\u{1b}[1m\u{1b}[4mb\u{1b}[0m".to_string())
+        );
+        assert_eq!(b.slice_visible(2..3), Ok("c".to_string()));
+        assert_eq!(b.slice_visible(3..), Ok("\u{1b}[31md\u{1b}[0m".to_string()));
+
+        // wide characters straddling the range are replaced with spaces
+        let b: Buffer = "東京e\u{301}x".into();
+        assert_eq!(b.slice_visible(1..5), Ok(" 京e\u{301}".to_string()));
+        assert_eq!(b.slice_visible(0..3), Ok("東 ".to_string()));
+        assert_eq!(b.slice_visible(1..2), Ok(" ".to_string()));
+
+        // every line is sliced on its own, the same way `visible_len` measures them
+        let b: Buffer = "\u{1b}[1mabc\nde\u{1b}[0m".into();
+        assert_eq!(
+            b.slice_visible(..b.visible_len().unwrap()),
+            Ok("\u{1b}[1mab\nde\u{1b}[0m".to_string())
+        );
+        assert_eq!(
+            b.slice_visible(1..),
+            Ok("\u{1b}[1mbc\ne\u{1b}[0m".to_string())
+        );
+        let b: Buffer = "\u{1b}[1mab\ncd\u{1b}[0m\r\nef".into();
+        assert_eq!(
+            b.slice_visible(..b.visible_len().unwrap()),
+            Ok(b.to_string().unwrap())
+        );
+        assert_eq!(
+            b.slice_visible(..1),
+            Ok("\u{1b}[1ma\nc\u{1b}[0m\r\ne".to_string())
+        );
+    }
+
+    #[test]
+    fn truncate_visible() {
+        let mut b: Buffer = "\u{1b}[31m東京\u{1b}[0m".into();
+        assert_eq!(
+            b.truncate_visible(4),
+            Ok("\u{1b}[31m東京\u{1b}[0m".to_string())
+        );
+        assert_eq!(
+            b.truncate_visible(3),
+            Ok("\u{1b}[31m東 \u{1b}[0m".to_string())
+        );
+        assert_eq!(b.visible_len(), Ok(3));
+        assert_eq!(b.truncate_visible(0), Ok(String::new()));
+        assert!(b.is_empty());
+    }
+
     #[test]
     fn boom() {
         let ch = '🤔';
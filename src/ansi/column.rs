@@ -0,0 +1,213 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::iter::VisibleChars;
+
+/// A character of the string indexed by a [`ColumnIndex`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Entry {
+    /// The byte offset of the character.
+    offset: usize,
+    /// The byte offset just past the character.
+    end: usize,
+    /// The column the character starts at.
+    column: usize,
+    /// The column the next character starts at.
+    next: usize,
+}
+
+/// A precomputed mapping between the **visible** columns and the byte offsets of a string, for
+/// when many lookups are done on the same string. See [`column_to_byte`] and [`byte_to_column`].
+///
+/// Columns are counted like [`visible_width`](`super::visible_width`) does: escape sequences take
+/// up no columns, wide characters take up two, tabs expand to the next multiple of 8 and columns
+/// restart after a `\n`.
+///
+/// # Examples
+/// ```
+/// # use reflors::ansi::ColumnIndex;
+/// let index = ColumnIndex::new("\x1b[1m東京\x1b[0m!");
+/// assert_eq!(index.column_to_byte(3), Some(7));
+/// assert_eq!(index.byte_to_column(7), Some(2));
+/// assert_eq!(index.byte_to_column(10), Some(4));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnIndex {
+    entries: Vec<Entry>,
+    /// The number of entries on the first line.
+    first_line: usize,
+    /// The byte offset the first line ends at.
+    first_line_end: usize,
+    len: usize,
+}
+
+impl ColumnIndex {
+    /// Creates a new [`ColumnIndex`] for the given `input`.
+    #[must_use]
+    pub fn new(input: &str) -> Self {
+        let entries = VisibleChars::new(input)
+            .map(|vis| Entry {
+                offset: vis.offset,
+                end: vis.offset + vis.ch.len_utf8(),
+                column: vis.column,
+                next: if vis.ch == '\n' {
+                    0
+                } else {
+                    vis.column + vis.width
+                },
+            })
+            .collect::<Vec<_>>();
+        let first_line = input.find('\n').map_or(entries.len(), |newline| {
+            entries.partition_point(|entry| entry.offset < newline)
+        });
+
+        Self {
+            first_line_end: entries
+                .get(first_line)
+                .map_or(input.len(), |entry| entry.offset),
+            entries,
+            first_line,
+            len: input.len(),
+        }
+    }
+
+    /// Gets the byte offset of the character covering the given **visible** `column` on the first
+    /// line. Escape sequences directly before the character aren't included, and a column in the
+    /// middle of a wide character maps to the start of that character.
+    ///
+    /// The column just past the end of the line maps to the end of the line, and any column
+    /// further than that maps to `None`.
+    #[must_use]
+    pub fn column_to_byte(&self, column: usize) -> Option<usize> {
+        let line = &self.entries[..self.first_line];
+        let width = line.last().map_or(0, |entry| entry.next);
+        if column >= width {
+            return (column == width).then_some(self.first_line_end);
+        }
+
+        let index = line.partition_point(|entry| entry.column <= column);
+        Some(line[index - 1].offset)
+    }
+
+    /// Gets the **visible** column the character at the given byte `offset` starts at. An offset
+    /// within an escape sequence maps to the column of the character after it, and an offset in the
+    /// middle of a character maps to the column of that character.
+    ///
+    /// The offset just past the end of the string maps to the column after the last character, and
+    /// any offset further than that maps to `None`.
+    #[must_use]
+    pub fn byte_to_column(&self, offset: usize) -> Option<usize> {
+        if offset > self.len {
+            return None;
+        }
+
+        // the last character starting at or before the offset
+        let index = self.entries.partition_point(|entry| entry.offset <= offset);
+        let column = match index.checked_sub(1).map(|index| self.entries[index]) {
+            Some(entry) if offset < entry.end => entry.column,
+            Some(entry) => entry.next,
+            None => 0,
+        };
+        Some(column)
+    }
+}
+
+/// Gets the byte offset of the character covering the given **visible** `column` on the first line
+/// of `input`, or `None` if the line isn't that wide. See [`ColumnIndex::column_to_byte`].
+///
+/// # Examples
+/// ```
+/// # use reflors::ansi::column_to_byte;
+/// assert_eq!(column_to_byte("\x1b[31mred\x1b[0m", 1), Some(6));
+/// assert_eq!(column_to_byte("東京", 3), Some(3));
+/// assert_eq!(column_to_byte("東京", 4), Some(6));
+/// assert_eq!(column_to_byte("東京", 5), None);
+/// ```
+#[must_use]
+pub fn column_to_byte(input: &str, column: usize) -> Option<usize> {
+    ColumnIndex::new(input).column_to_byte(column)
+}
+
+/// Gets the **visible** column the character at the given byte `offset` of `input` starts at, or
+/// `None` if the offset is past the end of `input`. See [`ColumnIndex::byte_to_column`].
+///
+/// # Examples
+/// ```
+/// # use reflors::ansi::byte_to_column;
+/// assert_eq!(byte_to_column("\x1b[31mred\x1b[0m", 6), Some(1));
+/// assert_eq!(byte_to_column("\x1b[31mred\x1b[0m", 2), Some(0));
+/// assert_eq!(byte_to_column("東京", 3), Some(2));
+/// assert_eq!(byte_to_column("one\ntwo", 5), Some(1));
+/// ```
+#[must_use]
+pub fn byte_to_column(input: &str, offset: usize) -> Option<usize> {
+    ColumnIndex::new(input).byte_to_column(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn mapping() {
+        let text = "a\u{1b}[1m東\u{1b}[0m\tb\u{301}\nxy";
+        let index = ColumnIndex::new(text);
+        let expected = [
+            (0, Some(0)),
+            (1, Some(5)),
+            (2, Some(5)),
+            (3, Some(12)),
+            (7, Some(12)),
+            (8, Some(13)),
+            (9, Some(16)),
+            (10, None),
+        ];
+        for (column, offset) in expected {
+            assert_eq!(index.column_to_byte(column), offset, "column {column}");
+            assert_eq!(column_to_byte(text, column), offset);
+        }
+
+        let expected = [
+            (0, Some(0)),
+            (1, Some(1)),
+            (5, Some(1)),
+            (6, Some(1)),
+            (8, Some(3)),
+            (12, Some(3)),
+            (13, Some(8)),
+            (14, Some(9)),
+            (16, Some(9)),
+            (17, Some(0)),
+            (18, Some(1)),
+            (19, Some(2)),
+            (20, None),
+        ];
+        for (offset, column) in expected {
+            assert_eq!(index.byte_to_column(offset), column, "offset {offset}");
+            assert_eq!(byte_to_column(text, offset), column);
+        }
+
+        assert_eq!(column_to_byte("", 0), Some(0));
+        assert_eq!(column_to_byte("\u{1b}[1m", 0), Some(4));
+        assert_eq!(byte_to_column("", 0), Some(0));
+    }
+
+    #[test]
+    fn inside() {
+        let text = "a\u{1b}[1m東\u{1b}[0m\tb\u{301}";
+        let index = ColumnIndex::new(text);
+        // an offset in the middle of a character maps to the column of that character
+        assert_eq!(index.byte_to_column(6), Some(1));
+        assert_eq!(index.byte_to_column(7), Some(1));
+        assert_eq!(index.byte_to_column(15), Some(9));
+        // and an offset within an escape sequence to the column of the character after it
+        assert_eq!(index.byte_to_column(2), Some(1));
+        assert_eq!(index.byte_to_column(4), Some(1));
+        assert_eq!(index.byte_to_column(10), Some(3));
+        assert_eq!(byte_to_column("a\u{1b}[1m", 3), Some(1));
+    }
+}
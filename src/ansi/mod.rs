@@ -1,5 +1,13 @@
 pub mod buffer;
+mod column;
+mod string;
 pub mod writer;
+pub use column::{byte_to_column, column_to_byte, ColumnIndex};
+pub use string::AnsiString;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::iter::{Token, Tokens};
 
 /// Marker for the start of an ansi escape code.
 pub const MARKER: char = '\u{1b}';
@@ -11,26 +19,79 @@ pub fn is_terminator(ch: char) -> bool {
     (0x40..=0x5a).contains(&u) || (0x61..=0x7a).contains(&u)
 }
 
-/// Returns the **visble** width of the given string, ignoring ansi escape sequences.
+/// Checks whether the given character takes up no space when displayed, i.e. soft hyphens
+/// (U+00AD), zero-width spaces and joiners (U+200B..=U+200D, U+2060, U+FEFF) and variation
+/// selectors.
+#[must_use]
+pub fn is_zero_width(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{AD}'
+            | '\u{200B}'..='\u{200D}'
+            | '\u{2060}'
+            | '\u{FEFF}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+/// Returns the **visible** width of a single (non-escape sequence) character, i.e. the number of
+/// columns it takes up in a terminal. This is two for wide characters (such as most CJK
+/// characters and emoji), and zero for control characters (such as `\r` and `\n`), combining
+/// marks and any of the characters matched by [`is_zero_width`].
+#[must_use]
+pub fn char_width(ch: char) -> usize {
+    if ch.is_control() || is_zero_width(ch) {
+        0
+    } else {
+        UnicodeWidthChar::width(ch).unwrap_or(0)
+    }
+}
+
+/// The number of columns between tab stops.
+pub const TAB_WIDTH: usize = 8;
+
+/// Returns the **visible** width of a single (non-escape sequence) character displayed at the
+/// given `column`. This is the same as its [`char_width`], except for tabs which extend to the
+/// next tab stop (every [`TAB_WIDTH`] columns).
 ///
-/// TODO: Figure out how to handle '\n' in this function (and in general)
+/// # Examples
+/// ```
+/// # use reflors::ansi::char_width_at;
+/// assert_eq!(char_width_at('a', 3), 1);
+/// assert_eq!(char_width_at('\t', 0), 8);
+/// assert_eq!(char_width_at('\t', 3), 5);
+/// ```
+#[must_use]
+pub fn char_width_at(ch: char, column: usize) -> usize {
+    if ch == '\t' {
+        TAB_WIDTH - column % TAB_WIDTH
+    } else {
+        char_width(ch)
+    }
+}
+
+/// Returns the **visble** width of the given string, ignoring ansi escape sequences. Tabs are
+/// expanded to the next tab stop (see [`char_width_at`]), and a `\n` starts counting again from
+/// zero, so the width of the last line is returned.
 #[must_use]
 pub fn visible_width(input: &str) -> usize {
-    let mut count = 0usize;
-    let mut in_ansi_seq = false;
-
-    for ch in input.chars() {
-        if ch == MARKER {
-            in_ansi_seq = true;
-        } else if in_ansi_seq {
-            if is_terminator(ch) {
-                in_ansi_seq = false;
-            }
-        } else {
-            match ch {
-                '\t' => count += 8 - (count % 8),
-                '\n' => count = 0,
-                _ => count += char::len_utf8(ch),
+    visible_width_from(0, input)
+}
+
+/// Returns the column the cursor ends up at when `input` is displayed starting at `column`,
+/// measured the same way as [`visible_width`].
+pub(crate) fn visible_width_from(column: usize, input: &str) -> usize {
+    let mut count = column;
+
+    for token in Tokens::new(input) {
+        if let Token::Text(text) = token {
+            for ch in text.chars() {
+                if ch == '\n' {
+                    count = 0;
+                } else {
+                    count += char_width_at(ch, count);
+                }
             }
         }
     }
@@ -39,12 +100,20 @@ pub fn visible_width(input: &str) -> usize {
 }
 
 /// Strips any ansi escape sequences from the given string.
-/// ## Panics
-/// Because it is not implemented.
-/// TODO: Write this function!
+///
+/// # Examples
+/// ```
+/// # use reflors::ansi::strip_ansi;
+/// assert_eq!(strip_ansi("\x1b[1;31mbold red\x1b[0m text"), "bold red text");
+/// ```
 #[must_use]
 pub fn strip_ansi(input: &str) -> String {
-    todo!("strip_ansi not implemented");
+    Tokens::new(input)
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(text),
+            Token::Escape { .. } => None,
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -64,6 +133,57 @@ mod tests {
             assert!(is_terminator(u as char));
         }
     }
+
+    #[test]
+    fn zero_width() {
+        assert_eq!(visible_width("soft\u{ad}ware"), 8);
+        assert_eq!(visible_width("a\u{200b}b\u{200d}c"), 3);
+        assert_eq!(visible_width("\u{1b}[1mx\u{fe0f}\u{1b}[0m"), 1);
+        assert_eq!(char_width('\u{2060}'), 0);
+        assert_eq!(char_width('a'), 1);
+        assert_eq!(char_width('\r'), 0);
+        assert_eq!(visible_width("abc\r"), 3);
+    }
+
+    #[test]
+    fn tabs() {
+        // every width calculation expands tabs to the next tab stop
+        assert_eq!(visible_width("\tx"), 9);
+        assert_eq!(visible_width("abc\tx"), 9);
+        assert_eq!(visible_width("\u{1b}[1m\tx\u{1b}[0m"), 9);
+    }
+
+    #[test]
+    fn osc() {
+        // a hyperlink is only as wide as its text, even though its url contains terminating
+        // characters
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(visible_width(link), 4);
+        assert_eq!(strip_ansi(link), "link");
+    }
+
+    #[test]
+    fn wide() {
+        assert_eq!(char_width('東'), 2);
+        assert_eq!(char_width('🤔'), 2);
+        assert_eq!(char_width('é'), 1);
+        assert_eq!(char_width('\u{301}'), 0);
+        assert_eq!(visible_width("\u{1b}[31m東京\u{1b}[0m!"), 5);
+        assert_eq!(visible_width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn strip() {
+        assert_eq!(strip_ansi("plain"), "plain");
+        assert_eq!(
+            strip_ansi("\u{1b}[38;2;249;38;114m你好\u{1b}[0m\n\u{1b}[1m"),
+            "你好\n"
+        );
+        assert_eq!(
+            strip_ansi("\u{1b}]8;;https://example.com\u{1b}\\link\u{1b}]8;;\u{1b}\\"),
+            "link"
+        );
+    }
 }
 
 // \u{1b}[1;4;38;2;255;255m
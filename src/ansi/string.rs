@@ -0,0 +1,462 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter, Result as FmtResult},
+    ops::{Add, AddAssign},
+};
+
+use super::buffer::Buffer;
+use crate::{
+    iter::{Token, Tokens},
+    Error, Result,
+};
+
+/// An owned string containing ansi escape sequences, which knows its **visible** width.
+///
+/// The width is measured once (the same way as [`visible_width`](`super::visible_width`)) and
+/// kept up to date as text is added, so it never has to be scanned for it again.
+///
+/// Two [`AnsiString`]s are equal when they look the same, i.e. they have the same characters in the
+/// same styles, even if they use different escape sequences to get there (e.g. `\x1b[1;31m`
+/// instead of `\x1b[1m\x1b[31m`, or a reset with nothing to reset).
+///
+/// # Examples
+/// ```
+/// # use reflors::ansi::AnsiString;
+/// let mut text = AnsiString::from("\x1b[31mred\x1b[0m");
+/// text += &AnsiString::from("\x1b[31m text\x1b[0m");
+/// assert_eq!(text.as_str(), "\x1b[31mred text\x1b[0m");
+/// assert_eq!(text.visible_len(), 8);
+/// assert_eq!(text, AnsiString::from("\x1b[0m\x1b[31mred\x1b[31m text\x1b[0m"));
+///
+/// let (left, right) = text.split_at_visible(4);
+/// assert_eq!(left.to_string(), "\x1b[31mred \x1b[0m");
+/// assert_eq!(right.to_string(), "\x1b[31mtext\x1b[0m");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AnsiString {
+    buffer: Buffer,
+    width: usize,
+}
+
+impl AnsiString {
+    /// Creates a new, empty, [`AnsiString`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the contents of this [`AnsiString`], escape sequences included.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        // SAFETY: the buffer is only ever filled from `str`s, so it is always valid utf8
+        unsafe { self.buffer.to_str_unchecked() }
+    }
+
+    /// Gets the **visible** width of this [`AnsiString`].
+    #[must_use]
+    pub fn visible_len(&self) -> usize {
+        self.width
+    }
+
+    /// Gets the length of this [`AnsiString`] in bytes, escape sequences included.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Checks whether this [`AnsiString`] is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Gets the underlying [`Buffer`].
+    #[must_use]
+    pub fn as_buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// Appends the given string to the end of this [`AnsiString`].
+    ///
+    /// If `s` starts by setting the same style that this [`AnsiString`] ends with, the two are
+    /// merged: both the reset at the end of this string (if any) and the style at the start of `s`
+    /// are dropped, since the style simply carries on.
+    pub fn push_str(&mut self, s: &str) {
+        self.width = super::visible_width_from(self.width, s);
+
+        let (style, rest) = leading_style(s);
+        if !style.is_empty() {
+            let (reset_at, active) = trailing_style(self.as_str());
+            if active == style {
+                if let Some(reset_at) = reset_at {
+                    let kept = Buffer::from(&self.as_str()[..reset_at]);
+                    self.buffer = kept;
+                }
+                self.buffer.push_str(rest);
+                return;
+            }
+        }
+
+        self.buffer.push_str(s);
+    }
+
+    /// Splits this [`AnsiString`] in two at the given **visible** column. Each half keeps the
+    /// styles that apply to it, and is reset at its end if needed (see
+    /// [`Buffer::slice_visible`]).
+    ///
+    /// A wide character straddling the column is replaced with a space on either side.
+    #[must_use]
+    pub fn split_at_visible(&self, column: usize) -> (Self, Self) {
+        // the buffer is always valid utf8, so slicing it can't fail
+        let left = self.buffer.slice_visible(..column).unwrap_or_default();
+        let right = self.buffer.slice_visible(column..).unwrap_or_default();
+        (Self::from(left), Self::from(right))
+    }
+
+    /// **Consumes** this [`AnsiString`] and returns its contents as a [`String`].
+    #[must_use]
+    pub fn into_string(self) -> String {
+        self.as_str().to_string()
+    }
+}
+
+impl Display for AnsiString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq for AnsiString {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && rendered(self.as_str()) == rendered(other.as_str())
+    }
+}
+
+impl Eq for AnsiString {}
+
+impl AsRef<str> for AnsiString {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for AnsiString {
+    fn from(s: &str) -> Self {
+        Self {
+            buffer: Buffer::from(s),
+            width: super::visible_width(s),
+        }
+    }
+}
+
+impl From<String> for AnsiString {
+    fn from(s: String) -> Self {
+        Self {
+            width: super::visible_width(&s),
+            buffer: Buffer::from(s),
+        }
+    }
+}
+
+impl From<&String> for AnsiString {
+    fn from(s: &String) -> Self {
+        Self::from(s.as_str())
+    }
+}
+
+impl TryFrom<Buffer> for AnsiString {
+    type Error = Error;
+
+    fn try_from(buffer: Buffer) -> Result<Self> {
+        Ok(Self {
+            width: super::visible_width(buffer.to_str()?),
+            buffer,
+        })
+    }
+}
+
+impl From<AnsiString> for String {
+    fn from(s: AnsiString) -> Self {
+        s.into_string()
+    }
+}
+
+impl From<AnsiString> for Buffer {
+    fn from(s: AnsiString) -> Self {
+        s.buffer
+    }
+}
+
+impl Add<&AnsiString> for AnsiString {
+    type Output = AnsiString;
+
+    fn add(mut self, rhs: &AnsiString) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign<&AnsiString> for AnsiString {
+    fn add_assign(&mut self, rhs: &AnsiString) {
+        self.push_str(rhs.as_str());
+    }
+}
+
+impl AddAssign<&str> for AnsiString {
+    fn add_assign(&mut self, rhs: &str) {
+        self.push_str(rhs);
+    }
+}
+
+/// Splits off the SGR sequences at the very start of `s`, returning them along with the rest.
+fn leading_style(s: &str) -> (&str, &str) {
+    let mut tokens = Tokens::new(s);
+    let mut end = 0;
+    while let Some(token) = tokens.next() {
+        if !token.is_sgr() || token.is_reset() {
+            break;
+        }
+        end = s.len() - tokens.remainder().len();
+    }
+
+    s.split_at(end)
+}
+
+/// Gets the SGR sequences (since the last reset) styling the end of `s`. If `s` ends with a reset,
+/// these are the styles that were active before it, and the offset of the reset is returned too.
+fn trailing_style(s: &str) -> (Option<usize>, String) {
+    let mut active = String::new();
+    let mut before_reset = String::new();
+    let mut reset = None;
+    let mut offset = 0;
+
+    for token in Tokens::new(s) {
+        if token.is_reset() {
+            if reset.is_none() {
+                reset = Some(offset);
+                before_reset = std::mem::take(&mut active);
+            }
+        } else {
+            reset = None;
+            if token.is_sgr() {
+                active.push_str(token.as_str());
+            }
+        }
+        offset += token.as_str().len();
+    }
+
+    match reset {
+        Some(_) => (reset, before_reset),
+        None => (None, active),
+    }
+}
+
+/// A piece of rendered text, used to compare [`AnsiString`]s regardless of redundant escapes.
+#[derive(PartialEq)]
+enum Rendered<'a> {
+    /// A character, along with the attributes styling it (keyed by what they control).
+    Char(char, BTreeMap<String, String>),
+    /// An escape sequence other than SGR, such as a cursor movement.
+    Escape(&'a str),
+}
+
+/// Gets the characters of `s` along with their styles, and its non-SGR escape sequences.
+fn rendered(s: &str) -> Vec<Rendered<'_>> {
+    let mut style = BTreeMap::new();
+    let mut output = Vec::new();
+
+    for token in Tokens::new(s) {
+        match token {
+            Token::Text(text) => {
+                output.extend(text.chars().map(|ch| Rendered::Char(ch, style.clone())));
+            }
+            Token::Escape { params, .. } if token.is_sgr() => apply_sgr(&mut style, params),
+            Token::Escape { raw, .. } => output.push(Rendered::Escape(raw)),
+        }
+    }
+
+    output
+}
+
+/// Applies the parameters of an SGR sequence to `style`, which maps what an attribute controls
+/// (e.g. the foreground color) to the attribute currently setting it.
+fn apply_sgr(style: &mut BTreeMap<String, String>, params: &str) {
+    let mut params = params.split(';').peekable();
+    while let Some(param) = params.next() {
+        // an empty parameter resets, and sub-parameters (like the curly underline `4:3`) don't
+        // change what the attribute controls
+        let code = match param.split(':').next().unwrap_or_default() {
+            "" => Some(0),
+            code => code.parse::<u16>().ok(),
+        };
+        let mut attribute = param.to_string();
+        if matches!(code, Some(38 | 48 | 58)) && !param.contains(':') {
+            // extended colors take their arguments along, `5;n` or `2;r;g;b`
+            let count = match params.peek() {
+                Some(&"5") => 2,
+                Some(&"2") => 4,
+                _ => 0,
+            };
+            for arg in params.by_ref().take(count) {
+                attribute.push(';');
+                attribute.push_str(arg);
+            }
+        }
+
+        let slot = match code {
+            Some(0) => {
+                style.clear();
+                continue;
+            }
+            Some(22) => {
+                // normal intensity turns off both bold and dim
+                style.remove("bold");
+                style.remove("dim");
+                continue;
+            }
+            Some(30..=39 | 90..=97) => "foreground",
+            Some(40..=49 | 100..=107) => "background",
+            Some(58 | 59) => "underline color",
+            Some(1) => "bold",
+            Some(2) => "dim",
+            Some(3 | 23) => "italic",
+            Some(4 | 24) => "underline",
+            Some(5 | 6 | 25) => "blink",
+            Some(7 | 27) => "reverse",
+            Some(8 | 28) => "hidden",
+            Some(9 | 29) => "strikethrough",
+            _ => param,
+        };
+        if matches!(code, Some(23..=29 | 39 | 49 | 59)) || param == "4:0" {
+            // turning an attribute off is the same as never having turned it on
+            style.remove(slot);
+        } else {
+            style.insert(slot.to_string(), attribute);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn width() {
+        assert_eq!(AnsiString::new().visible_len(), 0);
+        assert_eq!(AnsiString::from("\x1b[1m東京\x1b[0m!").visible_len(), 5);
+        let buffer = Buffer::from("e\u{301}\x1b[31m");
+        assert_eq!(AnsiString::try_from(buffer).unwrap().visible_len(), 1);
+
+        let text = AnsiString::from(String::from("\x1b[4mab\x1b[0m"));
+        assert_eq!(text.len(), 10);
+        assert_eq!(format!("{text}"), "\x1b[4mab\x1b[0m");
+        assert_eq!(String::from(text), "\x1b[4mab\x1b[0m");
+    }
+
+    #[test]
+    fn tabs() {
+        assert_eq!(AnsiString::from("\tx").visible_len(), 9);
+        // tabs are measured from the column they start at, also after pushing
+        let mut text = AnsiString::from("abc");
+        text.push_str("\tx");
+        assert_eq!(text.visible_len(), 9);
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(AnsiString::from(link).visible_len(), 4);
+    }
+
+    #[test]
+    fn concat() {
+        let red = AnsiString::from("\x1b[31mred\x1b[0m");
+        let merged = red.clone() + &AnsiString::from("\x1b[31m!\x1b[0m");
+        assert_eq!(merged.as_str(), "\x1b[31mred!\x1b[0m");
+        assert_eq!(merged.visible_len(), 4);
+
+        let mut open = AnsiString::from("\x1b[1m\x1b[31mred");
+        open += "\x1b[1m\x1b[31m!";
+        assert_eq!(open.as_str(), "\x1b[1m\x1b[31mred!");
+
+        let mut different = red.clone();
+        different += "\x1b[32mgreen\x1b[0m";
+        assert_eq!(different.as_str(), "\x1b[31mred\x1b[0m\x1b[32mgreen\x1b[0m");
+        assert_eq!(different.visible_len(), 8);
+
+        let mut plain = AnsiString::from("plain");
+        plain += &red;
+        assert_eq!(plain.as_str(), "plain\x1b[31mred\x1b[0m");
+    }
+
+    #[test]
+    fn split() {
+        let text = AnsiString::from("a\x1b[1m東京\x1b[0mb");
+        let (left, right) = text.split_at_visible(2);
+        assert_eq!(left.as_str(), "a\x1b[1m \x1b[0m");
+        assert_eq!(right.as_str(), "\x1b[1m 京\x1b[0mb");
+        assert_eq!((left.visible_len(), right.visible_len()), (2, 4));
+
+        let (left, right) = text.split_at_visible(10);
+        assert_eq!(left, text);
+        assert!(right.is_empty());
+    }
+
+    #[test]
+    fn equality() {
+        let text = AnsiString::from("\x1b[1;31mred\x1b[0m");
+        assert_eq!(text, AnsiString::from("\x1b[1m\x1b[31mred\x1b[0m"));
+        assert_eq!(
+            text,
+            AnsiString::from("\x1b[0m\x1b[31m\x1b[1mr\x1b[1med\x1b[m")
+        );
+        assert_eq!(text, AnsiString::from("\x1b[32;1;31mred\x1b[22;0;39m"));
+        assert_ne!(text, AnsiString::from("\x1b[31mred\x1b[0m"));
+        assert_ne!(text, AnsiString::from("\x1b[1;31mre\x1b[0md"));
+
+        assert_eq!(
+            AnsiString::from("\x1b[38;5;1ma\x1b[4;24mb"),
+            AnsiString::from("\x1b[38;5;1mab")
+        );
+        assert_ne!(
+            AnsiString::from("\x1b[38;5;1ma"),
+            AnsiString::from("\x1b[38;5;2ma")
+        );
+        assert_ne!(AnsiString::from("a\x1b[2Kb"), AnsiString::from("ab"));
+    }
+
+    #[test]
+    fn equality_attributes() {
+        // bold and dim can be combined, and normal intensity turns off both
+        let both = AnsiString::from("\x1b[1;2mx");
+        assert_ne!(both, AnsiString::from("\x1b[2mx"));
+        assert_ne!(both, AnsiString::from("\x1b[1mx"));
+        assert_eq!(both, AnsiString::from("\x1b[2m\x1b[1mx"));
+        assert_eq!(AnsiString::from("\x1b[1;2;22mx"), AnsiString::from("x"));
+
+        // sub-parameters keep the style they belong to instead of resetting it
+        let curly = AnsiString::from("\x1b[1;4:3mx");
+        assert_ne!(curly, AnsiString::from("\x1b[1;0mx"));
+        assert_ne!(curly, AnsiString::from("\x1b[1;4mx"));
+        assert_eq!(curly, AnsiString::from("\x1b[4:1;4:3;1mx"));
+        assert_eq!(AnsiString::from("\x1b[4:3;4:0mx"), AnsiString::from("x"));
+        assert_eq!(
+            AnsiString::from("\x1b[38:5:1ma"),
+            AnsiString::from("\x1b[31;38:5:1ma")
+        );
+
+        // unknown parameters don't reset the style either
+        assert_ne!(AnsiString::from("\x1b[1;x1mx"), AnsiString::from("x"));
+        assert_eq!(
+            AnsiString::from("\x1b[1;;31mx"),
+            AnsiString::from("\x1b[31mx")
+        );
+    }
+}
@@ -9,9 +9,10 @@
 //! and can be used to reset and restore that style.
 //!
 //! I am currently lacking a bit of direction on this struct, as there aren't a ton of examples of it being used in the go library. I've
-//! gone back and forth about what type the [`Writer::writer`] field should be, first having a [`Box<dyn std::io::Write>`], and then having
-//! a generic parameter [`W: std::io::Write`], and now I'm using the [`Writer`](bytes::buf::Writer) type, with a generic parameter for the
-//! type of underlying buffer used by the bytes version of writer.
+//! gone back and forth about what type the [`Writer::writer`] field should be, first having a [`Box<dyn std::io::Write>`], then the
+//! [`Writer`](bytes::buf::Writer) type with a generic parameter for its underlying buffer, and now it's back to a generic parameter
+//! `W: std::io::Write`, so it can write straight into stdout, files or sockets. `bytes` buffers can still be used through
+//! [`BufMut::writer`], and [`fmt::Write`](`std::fmt::Write`) targets through [`FmtWriter`](`crate::fmt::FmtWriter`).
 //!
 //! Source: [muesli/reflow/ansi/writer.go](https://github.com/muesli/reflow/blob/00a9f5c6902562434539e11d2c8f8d3dae851318/ansi/writer.go)
 
@@ -22,51 +23,79 @@ use std::{
 
 use bytes::{buf::Writer as BufWriter, BufMut, BytesMut};
 
-use crate::{Error, Result};
+use crate::{
+    fmt::Utf8Decoder,
+    iter::{EscapeBuffer, Token, Tokens},
+    Error, Result,
+};
 
-///
-pub struct Writer<B> {
-    writer: BufWriter<B>,
-    ansi: bool,
-    ansi_seq: bytes::BytesMut,
+/// An ansi-aware writer that keeps track of the last style written, so it can be
+/// [reset](`Writer::reset_ansi`) and [restored](`Writer::restore_ansi`) around other output.
+pub struct Writer<W> {
+    writer: W,
+    escapes: EscapeBuffer,
     last_seq: bytes::BytesMut,
     seq_changed: bool,
-    char_buff: [u8; 4],
+    decoder: Utf8Decoder,
 }
 
-impl<B> Writer<B> {
+impl<W> Writer<W> {
     /// Create a new `Writer` with the given internal `writer`.
     #[must_use]
-    pub fn new(writer: BufWriter<B>) -> Self {
+    pub fn new(writer: W) -> Self {
         Self {
             writer,
-            ansi: false,
-            ansi_seq: BytesMut::new(),
+            escapes: EscapeBuffer::default(),
             last_seq: BytesMut::new(),
             seq_changed: false,
-            char_buff: [0; 4],
+            decoder: Utf8Decoder::default(),
         }
     }
 
     /// Create a new [`ansi::Writer`](`Writer`) with the given capacity for its internal buffers.
     #[must_use]
-    pub fn with_capacity(writer: BufWriter<B>, cap: usize) -> Self {
+    pub fn with_capacity(writer: W, cap: usize) -> Self {
         Self {
             writer,
-            ansi: false,
-            ansi_seq: BytesMut::with_capacity(cap),
+            escapes: EscapeBuffer::default(),
             last_seq: BytesMut::with_capacity(cap),
             seq_changed: false,
-            char_buff: [0; 4],
+            decoder: Utf8Decoder::default(),
         }
     }
+
+    /// Gets a reference to the underlying writer.
+    #[must_use]
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Gets a mutable reference to the underlying writer. Writing to it directly bypasses the
+    /// tracking of ansi escape sequences.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// **Consumes** this [`Writer`] and returns the underlying writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use reflors::ansi::writer::Writer;
+    /// let mut buffer = Vec::new();
+    /// let writer = Writer::from_vec(buffer);
+    /// assert_eq!(writer.into_inner(), vec![]);
+    /// ```
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
 }
 
 impl Writer<Vec<u8>> {
     /// Creates a new [`Writer`] using the given `Vec` as its output buffer.
     #[must_use]
     pub fn from_vec(vec: Vec<u8>) -> Self {
-        Writer::new(vec.writer())
+        Writer::new(vec)
     }
 
     /// Creates a new [`Writer`] using the given `String` as its output buffer.
@@ -80,18 +109,18 @@ impl Writer<Vec<u8>> {
     /// ## Errors
     /// - `crate::Error::Utf8` if the buffer is not a valid utf8 string
     pub fn into_string(self) -> Result<String> {
-        String::from_utf8(self.writer.into_inner()).map_err(Error::from)
+        String::from_utf8(self.writer).map_err(Error::from)
     }
 }
 
 impl<'b> Writer<&'b mut [u8]> {
     /// Creates a new [`Writer`] using the given bytes as its output buffer.
     pub fn from_byte_slice(bytes: &'b mut [u8]) -> Self {
-        Writer::new(bytes.writer())
+        Writer::new(bytes)
     }
 }
 
-impl Writer<BytesMut> {
+impl Writer<BufWriter<BytesMut>> {
     /// Creates a new [`Writer`] using the given [`BytesMut`] as its output buffer.
     #[must_use]
     pub fn from_bytes(bytes: BytesMut) -> Self {
@@ -99,29 +128,17 @@ impl Writer<BytesMut> {
     }
 }
 
-impl<B: BufMut> Writer<B> {
-    /// **Consumes** this [`Writer`] and returns the underlying buffer.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// # use reflors::ansi::writer::Writer;
-    /// let mut buffer = Vec::new();
-    /// let writer = Writer::from_vec(buffer);
-    /// assert_eq!(writer.into_inner(), vec![]);
-    /// ```
-    pub fn into_inner(self) -> B {
-        self.writer.into_inner()
-    }
-
-    /// Write the given bytes to this [`ansi::Writer`]
+impl<W: IoWrite> Writer<W> {
+    /// Write the given bytes to this [`ansi::Writer`]. A utf8 character may be split across
+    /// writes, its start is held back until the rest of it is written.
     ///
     /// ## Errors
-    /// - `Error::Utf8` - If the conversion from `b` to `&str` fails
+    /// - `Error::Utf8` - If `b` can't be valid utf8
     /// - `Error::Io` - If writing to any of the internal buffers fails
     pub fn write_bytes(&mut self, b: &[u8]) -> Result<usize> {
-        let s = std::str::from_utf8(b)?;
-        self.write_str(s)
+        let s = self.decoder.decode(b)?;
+        self.write_str(&s)?;
+        Ok(b.len())
     }
 
     /// Write the given string to this [`Writer`]
@@ -129,29 +146,23 @@ impl<B: BufMut> Writer<B> {
     /// ## Errors
     /// - `Error::Io` - If writing to any of the internal buffers fails
     pub fn write_str(&mut self, s: &str) -> Result<usize> {
-        for ch in s.chars() {
-            if ch == super::MARKER {
-                self.ansi = true;
-                self.seq_changed = true;
-                self.ansi_seq.write_char(ch)?;
-            } else if self.ansi {
-                self.ansi_seq.write_char(ch)?;
-                if super::is_terminator(ch) {
-                    self.ansi = false;
-
-                    if self.ansi_seq.ends_with(b"[0m") {
-                        // reset sequence
+        // an escape sequence split across writes is held back until its terminator is written
+        let input = self.escapes.complete(s);
+        for token in Tokens::new(&input) {
+            match token {
+                Token::Text(text) => self.writer.write_all(text.as_bytes())?,
+                Token::Escape { raw, .. } => {
+                    if token.is_reset() {
                         self.last_seq.clear();
                         self.seq_changed = false;
-                    } else if ch == 'm' {
-                        // color code
-                        self.last_seq.put(self.ansi_seq.as_ref());
+                    } else if token.is_sgr() {
+                        // color code, other sequences (e.g. hyperlinks) don't change the style
+                        self.seq_changed = true;
+                        self.last_seq.put(raw.as_bytes());
                     }
 
-                    self.writer.write_all(self.ansi_seq.as_ref())?;
+                    self.writer.write_all(raw.as_bytes())?;
                 }
-            } else {
-                self.write_char(ch)?;
             }
         }
 
@@ -159,6 +170,15 @@ impl<B: BufMut> Writer<B> {
         Ok(s.len())
     }
 
+    /// Write the given bytes directly to the underlying writer, without keeping track of any ansi
+    /// escape sequences they contain (so they won't affect [`Writer::restore_ansi`]).
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn write_raw(&mut self, b: &[u8]) -> Result<()> {
+        self.writer.write_all(b).map_err(Error::from)
+    }
+
     /// Get the string value of the internal buffer [`Writer::last_seq`].
     ///
     /// ## Errors
@@ -201,24 +221,12 @@ impl<B: BufMut> Writer<B> {
         self.writer.flush().map_err(Error::from)
     }
 
-    fn write_char(&mut self, ch: char) -> Result<()> {
-        let s = ch.encode_utf8(self.char_buff.as_mut());
-        self.writer.write_all(s.as_ref())?;
-
-        // I dont think it is necessary to clear the char_buffer since we are never
-        //   using it directly, instead using the `&mut str` slice that is returned from
-        //   `char::encode_utf8`
-        // self.char_buff.copy_from_slice(&[0; 4]);
-
-        Ok(())
-    }
-
     // pub fn to_string(&self) -> Result<String> {
     //     String::from_utf8()
     // }
 }
 
-impl<B: BufMut + Sized> IoWrite for Writer<B> {
+impl<W: IoWrite> IoWrite for Writer<W> {
     fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
         use std::convert::Into;
         self.write_bytes(buf).map_err(Into::into)
@@ -240,7 +248,7 @@ mod tests {
         let buf_writer = vec.writer();
         let mut writer = Writer::new(buf_writer);
         assert_eq!(writer.write_str("Hello World!").unwrap(), 12);
-        vec = writer.into_inner();
+        vec = writer.into_inner().into_inner();
 
         assert_eq!(String::from_utf8(vec), Ok("Hello World!".to_string()));
     }
@@ -265,5 +273,30 @@ mod tests {
         // Works with String
         let mut writer = Writer::from_string(String::new());
         // Works with ansi::Buffer
+        // Works with any fmt::Write
+        let mut writer = Writer::new(crate::fmt::FmtWriter::new(String::new()));
+        writer.write_str("\x1b[1m你好\x1b[0m").unwrap();
+        assert_eq!(writer.into_inner().into_inner(), "\x1b[1m你好\x1b[0m");
+    }
+
+    #[test]
+    fn split_characters() {
+        // characters split across writes are held back until they are complete
+        let text = "\x1b[1m你好\x1b[0m";
+        for size in 1..text.len() {
+            let mut writer = Writer::from_vec(Vec::new());
+            for chunk in text.as_bytes().chunks(size) {
+                assert_eq!(writer.write_bytes(chunk).unwrap(), chunk.len());
+            }
+            assert_eq!(writer.into_string().unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        let mut writer = Writer::from_vec(Vec::<u8>::new());
+        writer.write_str(link).unwrap();
+        assert_eq!(writer.into_string().unwrap(), link);
     }
 }
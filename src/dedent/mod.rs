@@ -1,46 +1,48 @@
-use std::io::Write;
+mod options;
+mod writer;
+pub use options::{DedentOptions, DEFAULT_TAB_WIDTH};
+pub use writer::{Writer, DEFAULT_LOOKAHEAD};
 
-use bytes::BufMut;
-
-use crate::{Error, Result};
+use crate::{
+    iter::{Token, Tokens},
+    Result,
+};
 
 /// Detects the indentation level that is shared amongst all lines in the
 /// given text and removes it.
 ///
 /// ## Errors
+/// This function does not currently fail.
 pub fn dedent_str(input: &str) -> Result<String> {
-    let indent = detect_indent(input)?;
-    if indent == 0 {
-        return Ok(input.to_string());
-    }
-
-    dedent_impl(input, indent)
+    dedent_with(input, DedentOptions::default())
 }
 
 /// Detects the indentation level that is shared amongst all lines in the
 /// given text and removes it.
 ///
 /// ## Errors
+/// This function does not currently fail.
 pub fn dedent_string(input: String) -> Result<String> {
-    let indent = detect_indent(input.as_str())?;
-    if indent == 0 {
+    let options = DedentOptions::default();
+    if detect_indent(&input, options) == 0 {
         return Ok(input);
     }
 
-    dedent_impl(&input, indent)
+    dedent_with(&input, options)
 }
 
 /// Detects the indentation level that is shared amongst all lines in the
 /// given text and removes it.
 ///
 /// ## Errors
+/// This function does not currently fail.
 pub fn dedent_in_place(input: &mut String) -> Result<()> {
-    let indent = detect_indent(input.as_str())?;
-    if indent == 0 {
+    let options = DedentOptions::default();
+    if detect_indent(input, options) == 0 {
         return Ok(());
     }
 
-    *input = dedent_impl(input, indent)?;
+    *input = dedent_with(input, options)?;
     Ok(())
 }
 
@@ -54,64 +56,145 @@ pub fn dedent_to<W: std::io::Write>(input: &str, out: &mut W) -> Result<()> {
     Ok(())
 }
 
-fn detect_indent(input: &str) -> Result<usize> {
-    let mut current = 0usize;
-    let mut min = 0usize;
-    let mut append = true;
+/// Detects the indentation (in visible columns) shared amongst all lines in the given text and
+/// removes it, using the given [`options`](`DedentOptions`).
+///
+/// Tabs that straddle the common indentation are replaced by the spaces that are left over, so
+/// the remaining text keeps its alignment.
+///
+/// # Examples
+/// ```
+/// # use reflors::dedent::{dedent_with, DedentOptions};
+/// let text = "\n\tone\n\n        two\n";
+/// let output = dedent_with(text, DedentOptions::indoc().with_tab_width(8)).unwrap();
+/// assert_eq!(output, "one\n\ntwo\n");
+///
+/// // escape sequences before the indentation are kept
+/// let output = dedent_with("\x1b[1m  one\n    two", DedentOptions::new()).unwrap();
+/// assert_eq!(output, "\x1b[1mone\n  two");
+/// ```
+///
+/// ## Errors
+/// This function does not currently fail.
+pub fn dedent_with(input: &str, options: DedentOptions) -> Result<String> {
+    let input = if options.strip_first_empty_line() {
+        strip_first_empty_line(input)
+    } else {
+        input
+    };
 
-    for ch in input.chars() {
-        match ch {
-            ' ' | '\t' => {
-                if append {
-                    current += 1;
-                }
-            }
-            '\n' => {
-                current = 0;
-                append = true;
-            }
-            _ => {
-                if current > 0 && (min == 0 || current < min) {
-                    min = current;
-                    current = 0;
-                }
-                append = false;
+    let indent = detect_indent(input, options);
+    if indent == 0 {
+        return Ok(input.to_string());
+    }
+
+    let mut output = String::with_capacity(input.len());
+    for line in input.split_inclusive('\n') {
+        dedent_line(line, indent, options, &mut output);
+    }
+
+    Ok(output)
+}
+
+/// Detects the indentation, in visible columns, that is shared amongst all lines in the given
+/// text.
+///
+/// # Examples
+/// ```
+/// # use reflors::dedent::{detect_indent, DedentOptions};
+/// assert_eq!(detect_indent("    one\n\ttwo\n      \n", DedentOptions::new()), 4);
+/// assert_eq!(detect_indent("  one\n\n", DedentOptions::new().with_skip_blank_lines(false)), 0);
+/// ```
+#[must_use]
+pub fn detect_indent(input: &str, options: DedentOptions) -> usize {
+    input
+        .split_inclusive('\n')
+        .filter_map(|line| line_indent(line, options))
+        .min()
+        .unwrap_or(0)
+}
+
+/// Removes the first line of `input` if it is empty.
+pub(crate) fn strip_first_empty_line(input: &str) -> &str {
+    input
+        .strip_prefix('\n')
+        .or_else(|| input.strip_prefix("\r\n"))
+        .unwrap_or(input)
+}
+
+/// Measures the indentation of a single line in visible columns, or `None` if the line is blank
+/// and [`DedentOptions::skip_blank_lines`] is set.
+pub(crate) fn line_indent(line: &str, options: DedentOptions) -> Option<usize> {
+    let mut column = 0usize;
+
+    'tokens: for token in Tokens::new(line) {
+        let text = match token {
+            Token::Text(text) => text,
+            Token::Escape { .. } if options.leading_escapes() => continue,
+            Token::Escape { .. } => return Some(column),
+        };
+
+        for ch in text.chars() {
+            match ch {
+                ' ' => column += 1,
+                '\t' => column = options.next_tab_stop(column),
+                '\r' | '\n' => break 'tokens,
+                _ => return Some(column),
             }
         }
     }
 
-    Ok(min)
+    // the line is blank
+    (!options.skip_blank_lines()).then_some(column)
 }
 
-fn dedent_impl(input: &str, indent: usize) -> Result<String> {
-    use std::io::Write;
+/// Removes `indent` columns of leading whitespace from a single line (including its line ending)
+/// and appends the result to `output`.
+pub(crate) fn dedent_line(line: &str, indent: usize, options: DedentOptions, output: &mut String) {
+    let mut column = 0usize;
+    let mut rest = line.len();
+    let mut offset = 0;
+
+    'tokens: for token in Tokens::new(line) {
+        let text = match token {
+            Token::Escape { raw, .. } if column < indent && options.leading_escapes() => {
+                output.push_str(raw);
+                offset += raw.len();
+                continue;
+            }
+            Token::Escape { .. } => {
+                rest = offset;
+                break;
+            }
+            Token::Text(text) => text,
+        };
 
-    let mut omitted = 0usize;
-    let mut omit = true;
-    let mut buf = String::with_capacity(input.len());
+        for (i, ch) in text.char_indices() {
+            if column >= indent {
+                rest = offset + i;
+                break 'tokens;
+            }
 
-    for ch in input.chars() {
-        match ch {
-            ' ' | '\t' => {
-                if omit {
-                    if omitted < indent {
-                        omitted += 1;
-                        continue;
+            match ch {
+                ' ' => column += 1,
+                '\t' => {
+                    let next = options.next_tab_stop(column);
+                    if next > indent {
+                        // keep the part of the tab that extends past the indentation
+                        output.push_str(&" ".repeat(next - indent));
                     }
-                    omit = false;
+                    column = next;
+                }
+                _ => {
+                    rest = offset + i;
+                    break 'tokens;
                 }
-                buf.push(ch);
-            }
-            '\n' => {
-                omitted = 0;
-                omit = true;
-                buf.push(ch);
             }
-            _ => buf.push(ch),
         }
+        offset += text.len();
     }
 
-    Ok(buf)
+    output.push_str(&line[rest..]);
 }
 
 #[cfg(test)]
@@ -147,6 +230,12 @@ blah blah blah blah blah
         assert_eq!(output, output2);
     }
 
+    #[test]
+    fn crlf() {
+        let text = "    one\r\n      \r\n      two\r\n";
+        assert_eq!(dedent_str(text).unwrap(), "one\r\n  \r\n  two\r\n");
+    }
+
     #[test]
     fn basic_tabs() {
         #[rustfmt::skip]
@@ -158,4 +247,119 @@ blah blah blah blah blah
         assert_eq!(output2, EXPECTED);
         assert_eq!(output, output2);
     }
+
+    #[test]
+    fn mixed_tabs_and_spaces() {
+        let options = DedentOptions::new();
+        assert_eq!(
+            dedent_with("\tone\n        two\n          three", options).unwrap(),
+            "one\ntwo\n  three"
+        );
+        // a tab straddling the indentation leaves the rest of its width as spaces
+        assert_eq!(
+            dedent_with("  one\n\ttwo\n   \tthree", options).unwrap(),
+            "one\n      two\n \tthree"
+        );
+        assert_eq!(
+            dedent_with("\t\tone\n\ttwo", options.with_tab_width(2)).unwrap(),
+            "\tone\ntwo"
+        );
+    }
+
+    #[test]
+    fn blank_lines() {
+        let text = "    one\n\n  \n      two";
+        assert_eq!(
+            dedent_with(text, DedentOptions::new()).unwrap(),
+            "one\n\n\n  two"
+        );
+        assert_eq!(
+            dedent_with(text, DedentOptions::new().with_skip_blank_lines(false)).unwrap(),
+            text
+        );
+        assert_eq!(
+            dedent_with(
+                "  one\n   \n  two",
+                DedentOptions::new().with_skip_blank_lines(false)
+            )
+            .unwrap(),
+            "one\n \ntwo"
+        );
+    }
+
+    #[test]
+    fn first_empty_line() {
+        let text = "\n    one\n      two\n";
+        assert_eq!(dedent_str(text).unwrap(), "\none\n  two\n");
+        assert_eq!(
+            dedent_with(text, DedentOptions::indoc()).unwrap(),
+            "one\n  two\n"
+        );
+        assert_eq!(
+            dedent_with("\r\n  one\r\n", DedentOptions::indoc()).unwrap(),
+            "one\r\n"
+        );
+        // only an empty first line is stripped
+        assert_eq!(
+            dedent_with("  \n  one", DedentOptions::indoc()).unwrap(),
+            "\none"
+        );
+    }
+
+    #[test]
+    fn writer_chunks() {
+        // characters split across writes are held back until they are complete
+        let text = "\n      öne\r\n\n\t  twö\n    三\n        four";
+        let options = DedentOptions::indoc();
+        let expected = dedent_with(text, options).unwrap();
+        assert_eq!(expected, "  öne\r\n\n      twö\n三\n    four");
+        for size in 1..text.len() {
+            let mut writer = Writer::new(Vec::new(), options);
+            for chunk in text.as_bytes().chunks(size) {
+                assert_eq!(writer.write_bytes(chunk).unwrap(), chunk.len());
+            }
+            let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn writer_lookahead() {
+        let text = "    one\n\n      two\n  three\n";
+
+        // the third line is past the lookahead, so it can't lower the indentation
+        let mut writer = Writer::new(Vec::new(), DedentOptions::new()).with_lookahead(2);
+        writer.write_str(text).unwrap();
+        assert_eq!(writer.indent(), Some(4));
+        assert_eq!(
+            String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+            "one\n\n  two\nthree\n"
+        );
+
+        let mut writer = Writer::with_indent(Vec::new(), DedentOptions::new(), 2);
+        writer.write_str(text).unwrap();
+        assert_eq!(writer.get_ref().as_slice(), b"  one\n\n    two\nthree\n");
+    }
+
+    #[test]
+    fn ansi() {
+        let text = "\x1b[31m    red\x1b[0m\n  \x1b[1m    bold\x1b[0m";
+        assert_eq!(
+            dedent_with(text, DedentOptions::new()).unwrap(),
+            "\x1b[31mred\x1b[0m\n\x1b[1m  bold\x1b[0m"
+        );
+        assert_eq!(
+            dedent_with(text, DedentOptions::new().with_leading_escapes(false)).unwrap(),
+            text
+        );
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(
+            dedent_with(&format!("  {link}\n    {link}"), DedentOptions::new()).unwrap(),
+            format!("{link}\n  {link}")
+        );
+    }
 }
@@ -0,0 +1,119 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::ansi;
+
+/// The default width of a tab, in columns. This is the same as every other width calculation uses
+/// (see [`ansi::TAB_WIDTH`]), so the leftover parts of tabs line up with the rest of the text.
+pub const DEFAULT_TAB_WIDTH: usize = ansi::TAB_WIDTH;
+
+/// Options for determining how text should be dedented.
+///
+/// Indentation is measured in visible columns, so a tab and [`tab_width`](`DedentOptions::tab_width`)
+/// spaces count as the same indentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DedentOptions {
+    /// How many columns a tab advances to (tabs move to the next multiple of this).
+    tab_width: usize,
+    /// Whether whitespace-only lines are ignored when detecting the common indentation.
+    skip_blank_lines: bool,
+    /// Whether to remove the first line if it is empty, like the `indoc` crate.
+    strip_first_empty_line: bool,
+    /// Whether ansi escape sequences may come before (or in the middle of) the indentation.
+    leading_escapes: bool,
+}
+
+impl Default for DedentOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DedentOptions {
+    /// Creates a new [`DedentOptions`] with a tab width of [`DEFAULT_TAB_WIDTH`], which ignores
+    /// blank lines, keeps an empty first line and allows escape sequences before the indentation.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tab_width: DEFAULT_TAB_WIDTH,
+            skip_blank_lines: true,
+            strip_first_empty_line: false,
+            leading_escapes: true,
+        }
+    }
+
+    /// Creates a [`DedentOptions`] that behaves like the `indoc!` macro, i.e. it also strips the
+    /// first line if it is empty.
+    #[must_use]
+    pub const fn indoc() -> Self {
+        Self::new().with_strip_first_empty_line(true)
+    }
+}
+
+impl DedentOptions {
+    /// Sets how many columns a tab advances to. A width of 0 is treated as 1.
+    #[must_use]
+    pub const fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = if tab_width == 0 { 1 } else { tab_width };
+        self
+    }
+
+    /// Sets whether whitespace-only lines are ignored when detecting the common indentation. If
+    /// they are, blank lines that are shorter than the indentation are emptied entirely.
+    #[must_use]
+    pub const fn with_skip_blank_lines(mut self, skip_blank_lines: bool) -> Self {
+        self.skip_blank_lines = skip_blank_lines;
+        self
+    }
+
+    /// Sets whether the first line is removed if it is empty.
+    #[must_use]
+    pub const fn with_strip_first_empty_line(mut self, strip_first_empty_line: bool) -> Self {
+        self.strip_first_empty_line = strip_first_empty_line;
+        self
+    }
+
+    /// Sets whether ansi escape sequences may come before the indentation. If they may, they are
+    /// skipped over (and kept) while measuring and removing the indentation, otherwise they end
+    /// it like any other visible character.
+    #[must_use]
+    pub const fn with_leading_escapes(mut self, leading_escapes: bool) -> Self {
+        self.leading_escapes = leading_escapes;
+        self
+    }
+}
+
+impl DedentOptions {
+    /// Gets how many columns a tab advances to.
+    #[must_use]
+    pub const fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Gets whether whitespace-only lines are ignored when detecting the common indentation.
+    #[must_use]
+    pub const fn skip_blank_lines(&self) -> bool {
+        self.skip_blank_lines
+    }
+
+    /// Gets whether the first line is removed if it is empty.
+    #[must_use]
+    pub const fn strip_first_empty_line(&self) -> bool {
+        self.strip_first_empty_line
+    }
+
+    /// Gets whether ansi escape sequences may come before the indentation.
+    #[must_use]
+    pub const fn leading_escapes(&self) -> bool {
+        self.leading_escapes
+    }
+
+    /// Gets the column a tab starting at `column` advances to.
+    #[must_use]
+    pub const fn next_tab_stop(&self, column: usize) -> usize {
+        (column / self.tab_width + 1) * self.tab_width
+    }
+}
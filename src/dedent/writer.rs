@@ -0,0 +1,245 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io::{Result as IoResult, Write as IoWrite};
+
+use crate::{
+    fmt::Utf8Decoder,
+    pipeline::{self, Reflow},
+    Result,
+};
+
+use super::{dedent_line, line_indent, DedentOptions};
+
+/// The default number of (non-blank) lines a [`Writer`] looks at to detect the indentation.
+pub const DEFAULT_LOOKAHEAD: usize = 32;
+
+/// A streaming dedenting writer over any [`std::io::Write`].
+///
+/// The common indentation is either declared up front ([`Writer::with_indent`]) or detected from
+/// the first [`lookahead`](`Writer::with_lookahead`) non-blank lines, which are held back until
+/// then. Every line after that is dedented and written as soon as it is complete, so only the
+/// lookahead (and the current line) is ever kept in memory. Lines that turn out to be indented
+/// less than the detected indentation simply lose all of their indentation.
+///
+/// Remember to [`close`](`Writer::close`) the writer (or take its [inner](`Writer::into_inner`)
+/// writer) to write any lines that are still being held back.
+///
+/// # Examples
+/// ```
+/// # use reflors::dedent::{DedentOptions, Writer};
+/// # use std::io::Write;
+/// let mut writer = Writer::new(Vec::new(), DedentOptions::new());
+/// write!(writer, "    one\n      tw").unwrap();
+/// write!(writer, "o\n    three").unwrap();
+/// let output = writer.into_inner().unwrap();
+/// assert_eq!(String::from_utf8(output).unwrap(), "one\n  two\nthree");
+/// ```
+pub struct Writer<W> {
+    inner: W,
+    options: DedentOptions,
+    lookahead: usize,
+    declared: Option<usize>,
+    indent: Option<usize>,
+    detected: Option<usize>,
+    counted: usize,
+    lines: Vec<String>,
+    line: String,
+    first_line: bool,
+    decoder: Utf8Decoder,
+}
+
+impl<W> Writer<W> {
+    /// Creates a new [`Writer`] that detects the indentation from the first
+    /// [`DEFAULT_LOOKAHEAD`] non-blank lines and writes the dedented text to `inner`.
+    #[must_use]
+    pub fn new(inner: W, options: DedentOptions) -> Self {
+        Self {
+            inner,
+            options,
+            lookahead: DEFAULT_LOOKAHEAD,
+            declared: None,
+            indent: None,
+            detected: None,
+            counted: 0,
+            lines: Vec::new(),
+            line: String::new(),
+            first_line: true,
+            decoder: Utf8Decoder::default(),
+        }
+    }
+
+    /// Creates a new [`Writer`] that removes `indent` columns of indentation from every line,
+    /// without looking ahead at all.
+    #[must_use]
+    pub fn with_indent(inner: W, options: DedentOptions, indent: usize) -> Self {
+        Self {
+            declared: Some(indent),
+            indent: Some(indent),
+            ..Self::new(inner, options)
+        }
+    }
+
+    /// Sets how many non-blank lines are looked at to detect the indentation (at least 1). This
+    /// has no effect once the indentation is known.
+    #[must_use]
+    pub fn with_lookahead(mut self, lines: usize) -> Self {
+        self.lookahead = lines.max(1);
+        self
+    }
+
+    /// Gets the [`DedentOptions`] used by this [`Writer`].
+    #[must_use]
+    pub fn options(&self) -> &DedentOptions {
+        &self.options
+    }
+
+    /// Gets how many non-blank lines are looked at to detect the indentation.
+    #[must_use]
+    pub fn lookahead(&self) -> usize {
+        self.lookahead
+    }
+
+    /// Gets the indentation (in visible columns) that is removed from every line, or `None` if it
+    /// hasn't been determined yet.
+    #[must_use]
+    pub fn indent(&self) -> Option<usize> {
+        self.indent
+    }
+
+    /// Gets a reference to the underlying writer.
+    #[must_use]
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+}
+
+impl<W: IoWrite> Writer<W> {
+    /// Write the given bytes to this [`Writer`]. A utf8 character may be split across writes, its
+    /// start is held back until the rest of it is written.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If `bytes` can't be valid utf8
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
+        let s = self.decoder.decode(bytes)?;
+        self.write_str(&s)?;
+        Ok(bytes.len())
+    }
+
+    /// Write the given string to this [`Writer`].
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn write_str(&mut self, s: &str) -> Result<usize> {
+        for piece in s.split_inclusive('\n') {
+            self.line.push_str(piece);
+            if piece.ends_with('\n') {
+                let line = std::mem::take(&mut self.line);
+                self.push_line(line)?;
+            }
+        }
+
+        Ok(s.len())
+    }
+
+    /// Finishes writing by determining the indentation (if it is still unknown), writing every
+    /// line that is being held back and flushing the underlying writer.
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn close(&mut self) -> Result<()> {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.push_line(line)?;
+        }
+        if self.indent.is_none() {
+            self.resolve()?;
+        }
+
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    /// [Closes](`Writer::close`) this [`Writer`] and returns the underlying writer.
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn into_inner(mut self) -> Result<W> {
+        self.close()?;
+        Ok(self.inner)
+    }
+
+    fn push_line(&mut self, line: String) -> Result<()> {
+        if std::mem::take(&mut self.first_line)
+            && self.options.strip_first_empty_line()
+            && (line == "\n" || line == "\r\n")
+        {
+            return Ok(());
+        }
+
+        if let Some(indent) = self.indent {
+            return self.write_line(&line, indent);
+        }
+
+        if let Some(indent) = line_indent(&line, self.options) {
+            self.detected = Some(self.detected.map_or(indent, |min| min.min(indent)));
+            self.counted += 1;
+        }
+        self.lines.push(line);
+        if self.counted >= self.lookahead {
+            self.resolve()?;
+        }
+
+        Ok(())
+    }
+
+    /// Fixes the indentation to the one detected so far and writes the lines held back.
+    fn resolve(&mut self) -> Result<()> {
+        let indent = self.detected.unwrap_or(0);
+        self.indent = Some(indent);
+        for line in std::mem::take(&mut self.lines) {
+            self.write_line(&line, indent)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str, indent: usize) -> Result<()> {
+        let mut output = String::with_capacity(line.len());
+        dedent_line(line, indent, self.options, &mut output);
+        self.inner.write_all(output.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Reflow for Writer<Vec<u8>> {
+    fn write(&mut self, input: &str, out: &mut String) -> Result<()> {
+        self.write_str(input)?;
+        pipeline::drain(&mut self.inner, out)
+    }
+
+    fn finish(&mut self, out: &mut String) -> Result<()> {
+        self.close()?;
+        pipeline::drain(&mut self.inner, out)?;
+        let writer = match self.declared {
+            Some(indent) => Writer::with_indent(Vec::new(), self.options, indent),
+            None => Writer::new(Vec::new(), self.options),
+        };
+        *self = writer.with_lookahead(self.lookahead);
+        Ok(())
+    }
+}
+
+impl<W: IoWrite> IoWrite for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.write_bytes(buf).map_err(Into::into)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::{
+    ansi, dedent,
+    indent::{self, CustomIndent, IndentOptions},
+    padding::{self, Alignment},
+    truncate, wordwrap, wrap, Result,
+};
+
+/// Extension methods for doing the most common reflow operations on a string in a single call.
+///
+/// Every method delegates to the function of the same module (e.g. [`ReflowExt::word_wrap`] to
+/// [`wordwrap::wordwrap_str`]) using its default options, and returns the resulting string
+/// directly, since none of them can fail when working in memory.
+///
+/// This is implemented for anything that can be borrowed as a `str`, so it works on both `&str`
+/// and `String`. Truncating is called [`ReflowExt::truncate_visible`] so it doesn't clash with
+/// [`String::truncate`].
+///
+/// # Examples
+/// ```
+/// # use reflors::{indent::IndentOptions, ReflowExt};
+/// let text = "The \x1b[31mquick\x1b[0m brown fox";
+/// assert_eq!(text.visible_width(), 19);
+/// assert_eq!(text.strip_ansi(), "The quick brown fox");
+/// assert_eq!(
+///     text.word_wrap(10).indent(IndentOptions::two_spaces()).pad(12),
+///     "  The \x1b[31mquick\x1b[0m \n  brown fox "
+/// );
+/// assert_eq!(
+///     text.to_string().truncate_visible(12, "..."),
+///     "The \x1b[31mquick\x1b[0m..."
+/// );
+/// ```
+pub trait ReflowExt {
+    /// Gets the visible width, ignoring ansi escape sequences. See [`ansi::visible_width`].
+    fn visible_width(&self) -> usize;
+
+    /// Removes every ansi escape sequence. See [`ansi::strip_ansi`].
+    fn strip_ansi(&self) -> String;
+
+    /// Hard-wraps lines at `limit` columns. See [`wrap::wrap_str`].
+    fn wrap(&self, limit: usize) -> String;
+
+    /// Wraps lines at word boundaries so they fit in `limit` columns. See
+    /// [`wordwrap::wordwrap_str`].
+    fn word_wrap(&self, limit: usize) -> String;
+
+    /// Indents every line using the given [`IndentOptions`] (or [`CustomIndent`]). See
+    /// [`indent::indent_str`].
+    fn indent(&self, options: impl Into<CustomIndent>) -> String;
+
+    /// Removes the indentation shared by all lines. See [`dedent::dedent_str`].
+    fn dedent(&self) -> String;
+
+    /// Pads every line with spaces until it is `width` columns wide. See [`padding::pad_string`].
+    fn pad(&self, width: usize) -> String;
+
+    /// Truncates the text to `width` columns, ending it with `tail` if it had to be truncated. See
+    /// [`truncate::truncate_string`].
+    fn truncate_visible(&self, width: usize, tail: &str) -> String;
+
+    /// Aligns every line within `width` columns. See [`padding::align_str`].
+    fn align(&self, width: usize, alignment: Alignment) -> String;
+}
+
+impl<T: AsRef<str> + ?Sized> ReflowExt for T {
+    fn visible_width(&self) -> usize {
+        ansi::visible_width(self.as_ref())
+    }
+
+    fn strip_ansi(&self) -> String {
+        ansi::strip_ansi(self.as_ref())
+    }
+
+    fn wrap(&self, limit: usize) -> String {
+        infallible(wrap::wrap_str(self.as_ref(), limit))
+    }
+
+    fn word_wrap(&self, limit: usize) -> String {
+        infallible(wordwrap::wordwrap_str(
+            self.as_ref(),
+            &wordwrap::WrapOptions::new(limit),
+        ))
+    }
+
+    fn indent(&self, options: impl Into<CustomIndent>) -> String {
+        indent::indent_str(self.as_ref(), options, false)
+    }
+
+    fn dedent(&self) -> String {
+        infallible(dedent::dedent_str(self.as_ref()))
+    }
+
+    fn pad(&self, width: usize) -> String {
+        infallible(padding::pad_string(self.as_ref(), width))
+    }
+
+    fn truncate_visible(&self, width: usize, tail: &str) -> String {
+        infallible(truncate::truncate_string(self.as_ref(), width, tail))
+    }
+
+    fn align(&self, width: usize, alignment: Alignment) -> String {
+        padding::align_str(self.as_ref(), width, alignment)
+    }
+}
+
+/// Unwraps the result of an operation that only writes valid utf8 to memory, and so can't fail.
+fn infallible(result: Result<String>) -> String {
+    result.expect("reflowing a string in memory can't fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn delegates() {
+        let text = String::from("    one two\n      three");
+        assert_eq!(text.dedent(), "one two\n  three");
+        assert_eq!(text.dedent().wrap(5), "one t\nwo\n  thr\nee");
+        assert_eq!(text.dedent().word_wrap(7), "one two\n  three");
+        assert_eq!("one\ntwo".indent(IndentOptions::one_tab()), "\tone\n\ttwo");
+        assert_eq!("one\ntwo".align(5, Alignment::Right), "  one\n  two");
+        assert_eq!("one\ntwo".pad(4), "one \ntwo ");
+        assert_eq!(text.truncate_visible(6, "…"), "    o…");
+        assert_eq!(
+            "\x1b[1mbold\x1b[0m".truncate_visible(3, "."),
+            "\x1b[1mbo.\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+mod visible;
+mod writer;
+pub use visible::Visible;
+pub use writer::FmtWriter;
+pub(crate) use writer::Utf8Decoder;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    #[test]
+    fn split_characters() {
+        let text = "你好 reflow";
+        for size in 1..text.len() {
+            let mut writer = FmtWriter::new(String::new());
+            for chunk in text.as_bytes().chunks(size) {
+                assert_eq!(writer.write(chunk).unwrap(), chunk.len());
+            }
+            assert_eq!(writer.into_inner(), text);
+        }
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mut writer = FmtWriter::new(String::new());
+        let err = writer.write(b"ab\xffcd").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn visible() {
+        let text = "\x1b[1mbold\x1b[0m and \x1b[4mline\x1b[0m";
+        assert_eq!(format!("{}", Visible(text)), text);
+        assert_eq!(format!("{:10}|", Visible(text)), format!("{}|", text));
+        assert_eq!(format!("{:<16}|", Visible(text)), format!("{}   |", text));
+        assert_eq!(format!("{:*>15}", Visible(text)), format!("**{}", text));
+        assert_eq!(format!("{:^6}", Visible(String::from("ab"))), "  ab  ");
+        assert_eq!(format!("{:^5}", Visible("ab")), " ab  ");
+
+        // precision truncates by visible width and closes the style
+        assert_eq!(format!("{:.6}", Visible(text)), "\x1b[1mbold\x1b[0m a");
+        assert_eq!(format!("{:.2}", Visible(text)), "\x1b[1mbo\x1b[0m");
+        assert_eq!(format!("{:>4.3}", Visible(text)), " \x1b[1mbol\x1b[0m");
+        assert_eq!(format!("{:.20}", Visible(text)), text);
+
+        // plain strings work the same as they do without the wrapper
+        for s in ["", "plain", "plain text"] {
+            assert_eq!(format!("{:>7.4}", Visible(s)), format!("{:>7.4}", s));
+            assert_eq!(format!("{:_^8}", Visible(s)), format!("{:_^8}", s));
+        }
+    }
+
+    #[test]
+    fn formatter() {
+        struct Indented(&'static str);
+
+        impl std::fmt::Display for Indented {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let options = crate::indent::IndentOptions::two_spaces();
+                let mut writer = crate::indent::Writer::new_piped(options, FmtWriter::new(f));
+                writer.write_str(self.0).map_err(|_| std::fmt::Error)?;
+                writer.close().map_err(|_| std::fmt::Error)
+            }
+        }
+
+        assert_eq!(Indented("One\nTwo").to_string(), "  One\n  Two");
+    }
+}
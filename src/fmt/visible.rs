@@ -0,0 +1,67 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    borrow::Cow,
+    fmt::{Alignment, Display, Formatter, Result as FmtResult, Write as FmtWrite},
+};
+
+use crate::{ansi, truncate};
+
+/// A wrapper around a string containing ansi escape sequences, whose [`Display`] implementation
+/// measures the string by its **visible** width.
+///
+/// The width, fill and alignment of the [`Formatter`] work like they do for a plain `str`, so
+/// `format!("{:<20}", Visible(colored))` pads the text to 20 visible columns no matter how many
+/// escape sequences it contains. The precision truncates the text to that many visible columns
+/// (using [`truncate::truncate_string`]), keeping its escape sequences intact.
+///
+/// # Examples
+/// ```
+/// # use reflors::fmt::Visible;
+/// let red = "\x1b[31mred\x1b[0m";
+/// assert_eq!(format!("[{:>5}]", Visible(red)), "[  \x1b[31mred\x1b[0m]");
+/// assert_eq!(format!("[{:-^7}]", Visible(red)), "[--\x1b[31mred\x1b[0m--]");
+/// assert_eq!(format!("[{:4.2}]", Visible(red)), "[\x1b[31mre\x1b[0m  ]");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Visible<T>(pub T);
+
+impl<T: AsRef<str>> Display for Visible<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut text = Cow::Borrowed(self.0.as_ref());
+        if let Some(precision) = f.precision() {
+            if ansi::visible_width(&text) > precision {
+                text = Cow::Owned(
+                    truncate::truncate_string(&text, precision, "").map_err(|_| std::fmt::Error)?,
+                );
+            }
+        }
+
+        let padding = f
+            .width()
+            .map_or(0, |width| width.saturating_sub(ansi::visible_width(&text)));
+        if padding == 0 {
+            return f.write_str(&text);
+        }
+
+        let left = match f.align() {
+            None | Some(Alignment::Left) => 0,
+            Some(Alignment::Right) => padding,
+            Some(Alignment::Center) => padding / 2,
+        };
+        let fill = f.fill();
+        for _ in 0..left {
+            f.write_char(fill)?;
+        }
+        f.write_str(&text)?;
+        for _ in left..padding {
+            f.write_char(fill)?;
+        }
+
+        Ok(())
+    }
+}
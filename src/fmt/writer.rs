@@ -0,0 +1,115 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    borrow::Cow,
+    fmt::Write as FmtWrite,
+    io::{Error as IoError, ErrorKind, Result as IoResult, Write as IoWrite},
+    str::Utf8Error,
+};
+
+/// An adapter that lets any [`std::fmt::Write`] target (such as a [`String`] or a
+/// [`Formatter`](`std::fmt::Formatter`)) be used as the [`std::io::Write`] sink of the writers in
+/// this crate.
+///
+/// Bytes are passed on as soon as they form complete utf8 characters, so characters may be split
+/// across writes. Bytes that can never be valid utf8 are rejected with an
+/// [`ErrorKind::InvalidData`] error.
+///
+/// # Examples
+/// ```
+/// # use reflors::{fmt::FmtWriter, indent::{IndentOptions, Writer}};
+/// # use std::io::Write;
+/// let mut writer = Writer::new_piped(IndentOptions::two_spaces(), FmtWriter::new(String::new()));
+/// write!(writer, "One\nTwo").unwrap();
+/// assert_eq!(writer.into_inner().into_inner(), "  One\n  Two");
+/// ```
+#[derive(Debug, Default)]
+pub struct FmtWriter<F> {
+    inner: F,
+    decoder: Utf8Decoder,
+}
+
+impl<F> FmtWriter<F> {
+    /// Creates a new [`FmtWriter`] writing to `inner`.
+    #[must_use]
+    pub fn new(inner: F) -> Self {
+        Self {
+            inner,
+            decoder: Utf8Decoder::default(),
+        }
+    }
+
+    /// Gets a reference to the underlying [`std::fmt::Write`] target.
+    #[must_use]
+    pub fn get_ref(&self) -> &F {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying [`std::fmt::Write`] target.
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.inner
+    }
+
+    /// **Consumes** this [`FmtWriter`] and returns the underlying [`std::fmt::Write`] target.
+    /// Any incomplete utf8 character that was written last is discarded.
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F: FmtWrite> IoWrite for FmtWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let s = self
+            .decoder
+            .decode(buf)
+            .map_err(|err| IoError::new(ErrorKind::InvalidData, err))?;
+        self.inner.write_str(&s).map_err(IoError::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+/// Decodes utf8 that is written in arbitrarily sized chunks. A character that is split across
+/// writes is held back until the rest of it is written, while bytes that can never be valid utf8
+/// are rejected.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Utf8Decoder {
+    /// The start of a utf8 character that hasn't been completely written yet.
+    partial: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    /// Decodes the next chunk of `bytes`, prefixed with the incomplete character held back from
+    /// the previous chunk (if any).
+    pub(crate) fn decode<'a>(&mut self, bytes: &'a [u8]) -> Result<Cow<'a, str>, Utf8Error> {
+        if self.partial.is_empty() {
+            return Self::split(bytes, &mut self.partial).map(Cow::Borrowed);
+        }
+
+        let mut joined = std::mem::take(&mut self.partial);
+        joined.extend_from_slice(bytes);
+        Self::split(&joined, &mut self.partial).map(|s| Cow::Owned(s.to_string()))
+    }
+
+    /// Returns the complete characters at the start of `bytes`, moving an incomplete character at
+    /// its end into `partial`.
+    fn split<'a>(bytes: &'a [u8], partial: &mut Vec<u8>) -> Result<&'a str, Utf8Error> {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            // the input ends in the middle of a character, which is held back until it's complete
+            Err(err) if err.error_len().is_none() => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                partial.extend_from_slice(rest);
+                std::str::from_utf8(valid)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
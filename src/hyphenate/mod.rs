@@ -0,0 +1,353 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+use crate::iter::{Token, Tokens};
+
+/// The soft hyphen character (U+00AD), which marks a place where a word may be hyphenated.
+pub const SOFT_HYPHEN: char = '\u{AD}';
+
+/// The US English hyphenation patterns from `hyph-en-us.tex` (see `en_us.txt` for their copyright
+/// and permission notice), one per line.
+pub const EN_US_PATTERNS: &str = include_str!("en_us.txt");
+
+/// The hyphenation exceptions that accompany [`EN_US_PATTERNS`].
+pub const EN_US_EXCEPTIONS: &str = "as-so-ciate as-so-ciates dec-li-na-tion oblig-a-tory \
+    phil-an-thropic present presents project projects reci-procity re-cog-ni-zance ref-or-ma-tion \
+    ret-ri-bu-tion ta-ble";
+
+/// A Liang-style (as used by TeX) hyphenator, which finds the places a word may be hyphenated at
+/// using a set of patterns and a list of exceptions.
+///
+/// Words that already contain soft hyphens (U+00AD) are only ever hyphenated at those soft
+/// hyphens, so a [`Hyphenator`] without any patterns simply honors the existing soft hyphens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hyphenator {
+    /// Maps the letters of each pattern to the levels between them.
+    patterns: HashMap<String, Vec<u8>>,
+    /// Maps each exceptional word to the character positions it may be hyphenated at.
+    exceptions: HashMap<String, Vec<usize>>,
+    /// The length (in characters) of the longest pattern.
+    max_len: usize,
+    /// The minimum number of characters before a hyphen.
+    left_min: usize,
+    /// The minimum number of characters after a hyphen.
+    right_min: usize,
+}
+
+impl Default for Hyphenator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hyphenator {
+    /// Creates a new [`Hyphenator`] without any patterns, which only honors soft hyphens.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            patterns: HashMap::new(),
+            exceptions: HashMap::new(),
+            max_len: 0,
+            left_min: 2,
+            right_min: 3,
+        }
+    }
+
+    /// Creates a new [`Hyphenator`] using the embedded US English patterns and exceptions.
+    ///
+    /// # Examples
+    /// ```
+    /// # use reflors::hyphenate::Hyphenator;
+    /// let hyphenator = Hyphenator::english();
+    /// assert_eq!(hyphenator.hyphenate_with("hyphenation", "-"), "hy-phen-ation");
+    /// ```
+    #[must_use]
+    pub fn english() -> Self {
+        Self::new()
+            .with_patterns(EN_US_PATTERNS)
+            .with_exceptions(EN_US_EXCEPTIONS)
+    }
+
+    /// Adds the given patterns to this [`Hyphenator`]. Patterns are separated by whitespace and
+    /// use the TeX format (e.g. `.hy3ph` or `a1b2c`), anything following a `%` on a line is
+    /// treated as a comment.
+    #[must_use]
+    pub fn with_patterns(mut self, patterns: &str) -> Self {
+        for pattern in patterns.lines().flat_map(|line| {
+            line.split('%')
+                .next()
+                .unwrap_or_default()
+                .split_whitespace()
+        }) {
+            let mut letters = String::with_capacity(pattern.len());
+            let mut levels = vec![0u8];
+            for ch in pattern.chars() {
+                if let Some(level) = ch.to_digit(10) {
+                    // Digits are always less than 10
+                    #[allow(clippy::cast_possible_truncation)]
+                    let level = level as u8;
+                    if let Some(last) = levels.last_mut() {
+                        *last = level;
+                    }
+                } else {
+                    letters.push(ch);
+                    levels.push(0);
+                }
+            }
+
+            self.max_len = self.max_len.max(levels.len() - 1);
+            self.patterns.insert(letters, levels);
+        }
+
+        self
+    }
+
+    /// Adds the given exceptions to this [`Hyphenator`]. Exceptions are separated by whitespace
+    /// and are written as the full word with a `-` at each place it may be hyphenated at
+    /// (e.g. `ta-ble`), or with no `-` at all if it should never be hyphenated.
+    #[must_use]
+    pub fn with_exceptions(mut self, exceptions: &str) -> Self {
+        for exception in exceptions.split_whitespace() {
+            let mut word = String::with_capacity(exception.len());
+            let mut positions = Vec::new();
+            let mut count = 0usize;
+            for ch in exception.chars() {
+                if ch == '-' {
+                    positions.push(count);
+                } else {
+                    word.extend(ch.to_lowercase());
+                    count += 1;
+                }
+            }
+
+            self.exceptions.insert(word, positions);
+        }
+
+        self
+    }
+
+    /// Sets the minimum number of characters that must come before (`left`) and after (`right`)
+    /// a hyphen. The defaults are 2 and 3.
+    #[must_use]
+    pub fn with_min(mut self, left: usize, right: usize) -> Self {
+        self.left_min = left.max(1);
+        self.right_min = right.max(1);
+        self
+    }
+
+    /// Finds the places the given `word` may be hyphenated at, returned as the byte offsets that
+    /// each following piece of the word starts at. Ansi escape sequences are ignored, and any
+    /// escape sequences directly preceding a hyphenation point are kept with the following piece.
+    ///
+    /// If the word contains soft hyphens, only the places directly following them are returned
+    /// and the caller is expected to remove the soft hyphens themselves. Otherwise each run of
+    /// alphabetic characters is hyphenated using the patterns, so surrounding punctuation is
+    /// never split off.
+    ///
+    /// # Examples
+    /// ```
+    /// # use reflors::hyphenate::Hyphenator;
+    /// let hyphenator = Hyphenator::english();
+    /// assert_eq!(hyphenator.hyphenate("hyphenation"), vec![2, 6]);
+    /// assert_eq!(hyphenator.hyphenate("\u{1b}[1mhyphen\u{1b}[0mation"), vec![6, 10]);
+    /// assert_eq!(hyphenator.hyphenate("re\u{ad}use"), vec![4]);
+    /// ```
+    #[must_use]
+    pub fn hyphenate(&self, word: &str) -> Vec<usize> {
+        let visible = visible_chars(word);
+
+        if visible.iter().any(|(_, ch)| *ch == SOFT_HYPHEN) {
+            return visible
+                .windows(2)
+                .filter(|pair| pair[0].1 == SOFT_HYPHEN)
+                .map(|pair| pair[1].0)
+                .collect();
+        }
+
+        let mut points = Vec::new();
+        let mut start = 0;
+        while start < visible.len() {
+            if !visible[start].1.is_alphabetic() {
+                start += 1;
+                continue;
+            }
+
+            let end = visible[start..]
+                .iter()
+                .position(|(_, ch)| !ch.is_alphabetic())
+                .map_or(visible.len(), |len| start + len);
+            let run = &visible[start..end];
+            points.extend(self.hyphenate_run(run).into_iter().map(|i| run[i].0));
+            start = end;
+        }
+
+        points
+    }
+
+    /// Hyphenates the given `word`, inserting `hyphen` at every place it may be hyphenated at.
+    ///
+    /// # Examples
+    /// ```
+    /// # use reflors::hyphenate::Hyphenator;
+    /// let hyphenator = Hyphenator::english();
+    /// assert_eq!(hyphenator.hyphenate_with("table", "-"), "ta-ble");
+    /// assert_eq!(hyphenator.hyphenate_with("(paragraphs)", "·"), "(para·graphs)");
+    /// ```
+    #[must_use]
+    pub fn hyphenate_with(&self, word: &str, hyphen: &str) -> String {
+        let mut output = String::with_capacity(word.len());
+        let mut start = 0;
+        for point in self.hyphenate(word) {
+            output.push_str(&word[start..point]);
+            output.push_str(hyphen);
+            start = point;
+        }
+        output.push_str(&word[start..]);
+        output.retain(|ch| ch != SOFT_HYPHEN);
+        output
+    }
+
+    /// Finds the character positions in a run of letters that it may be hyphenated at.
+    fn hyphenate_run(&self, run: &[(usize, char)]) -> Vec<usize> {
+        let len = run.len();
+        if len < self.left_min + self.right_min {
+            return Vec::new();
+        }
+
+        let lower = run
+            .iter()
+            .map(|(_, ch)| ch.to_lowercase().next().unwrap_or(*ch))
+            .collect::<String>();
+        if let Some(positions) = self.exceptions.get(&lower) {
+            return positions.clone();
+        }
+
+        let dotted = std::iter::once('.')
+            .chain(lower.chars())
+            .chain(std::iter::once('.'))
+            .collect::<Vec<_>>();
+        let mut levels = vec![0u8; dotted.len() + 1];
+        let mut key = String::new();
+        for i in 0..dotted.len() {
+            key.clear();
+            for &ch in dotted.iter().skip(i).take(self.max_len) {
+                key.push(ch);
+                if let Some(pattern) = self.patterns.get(&key) {
+                    for (k, level) in pattern.iter().enumerate() {
+                        levels[i + k] = levels[i + k].max(*level);
+                    }
+                }
+            }
+        }
+
+        // levels[p + 1] is the level between the characters p - 1 and p of the run
+        (self.left_min..=len - self.right_min)
+            .filter(|p| levels[p + 1] % 2 == 1)
+            .collect()
+    }
+}
+
+/// Collects the visible characters of `input` along with the byte offset of the escape sequences
+/// directly preceding them (or of the character itself if there are none).
+pub(crate) fn visible_chars(input: &str) -> Vec<(usize, char)> {
+    let mut chars = Vec::with_capacity(input.len());
+    let mut escape_start = None;
+    let mut offset = 0;
+
+    for token in Tokens::new(input) {
+        match token {
+            Token::Text(text) => {
+                for (i, ch) in text.char_indices() {
+                    chars.push((escape_start.take().unwrap_or(offset + i), ch));
+                }
+            }
+            Token::Escape { .. } => {
+                escape_start.get_or_insert(offset);
+            }
+        }
+        offset += token.as_str().len();
+    }
+
+    chars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn english() {
+        let hyphenator = Hyphenator::english();
+        let cases = [
+            ("hyphenation", "hy-phen-ation"),
+            ("extensive", "ex-ten-sive"),
+            ("computer", "com-puter"),
+            ("algorithm", "al-go-rithm"),
+            ("associate", "as-so-ciate"),
+            ("project", "project"),
+            ("Typography", "Ty-pog-ra-phy"),
+            ("cat", "cat"),
+        ];
+        for (word, expected) in cases {
+            assert_eq!(hyphenator.hyphenate_with(word, "-"), expected, "{}", word);
+        }
+    }
+
+    #[test]
+    fn custom_patterns() {
+        let hyphenator = Hyphenator::new()
+            .with_patterns("% split between doubled letters\nb1b l1l")
+            .with_exceptions("bal-loon")
+            .with_min(1, 1);
+        assert_eq!(hyphenator.hyphenate_with("rubber", "-"), "rub-ber");
+        assert_eq!(hyphenator.hyphenate_with("balloon", "-"), "bal-loon");
+        assert_eq!(hyphenator.hyphenate_with("all", "-"), "al-l");
+    }
+
+    #[test]
+    fn soft_hyphens() {
+        let hyphenator = Hyphenator::english();
+        assert_eq!(
+            hyphenator.hyphenate_with("hyp\u{ad}henation", "-"),
+            "hyp-henation"
+        );
+        assert_eq!(
+            Hyphenator::new().hyphenate_with("hyphenation", "-"),
+            "hyphenation"
+        );
+        assert_eq!(
+            Hyphenator::new().hyphenate_with("soft\u{ad}ware\u{ad}", "-"),
+            "soft-ware"
+        );
+    }
+
+    #[test]
+    fn ansi() {
+        let hyphenator = Hyphenator::english();
+        assert_eq!(
+            hyphenator.hyphenate_with("\u{1b}[31mhyphenation\u{1b}[0m", "-"),
+            "\u{1b}[31mhy-phen-ation\u{1b}[0m"
+        );
+        assert_eq!(
+            hyphenator.hyphenate_with("\u{1b}[31mhy\u{1b}[1mphenation\u{1b}[0m", "-"),
+            "\u{1b}[31mhy-\u{1b}[1mphen-ation\u{1b}[0m"
+        );
+    }
+
+    #[test]
+    fn osc() {
+        // the url of a hyperlink is not hyphenated
+        let hyphenator = Hyphenator::english();
+        let open = "\u{1b}]8;;https://example.com/hyphenation\u{1b}\\";
+        assert_eq!(
+            hyphenator.hyphenate_with(&format!("{open}hyphenation\u{1b}]8;;\u{1b}\\"), "-"),
+            format!("{open}hy-phen-ation\u{1b}]8;;\u{1b}\\")
+        );
+    }
+}
@@ -5,11 +5,20 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 mod options;
-pub use options::{IndentOptions, IndentStyle, LineEnding};
+mod writer;
+pub use options::{CustomIndent, IndentOptions, IndentStyle, LineEnding};
+pub use writer::Writer;
 
-/// Indent the given `input` string using the given [`options`](`IndentOptions`). If `checked`
-/// is true, each line will be checked to see if it already contains the correct indentation,
-/// and if so it will be returned unmodified, otherwise it will be indented regardless.
+use crate::ansi;
+
+/// Indent the given `input` string using the given [`options`](`IndentOptions`). The line endings
+/// (including a trailing one) are converted to [`IndentOptions::line_endings`] if they are set, and
+/// kept as they are otherwise. If `checked` is true, each line will be checked to see if it already
+/// contains the correct indentation, and if so it will be returned unmodified, otherwise it will be
+/// indented regardless. A [`CustomIndent`] may be given instead of the [`IndentOptions`].
+///
+/// If the input contains ansi escape sequences the indentation is isolated from their style (see
+/// [`indent_str_styled`]), and only the visible text of each line is checked.
 ///
 /// # Examples
 /// ```
@@ -23,19 +32,34 @@ pub use options::{IndentOptions, IndentStyle, LineEnding};
 /// assert_eq!(four_spaces, "    One\n    Two\n    Three");
 /// ```
 #[must_use]
-pub fn indent_str(input: &str, options: IndentOptions, checked: bool) -> String {
-    let sep = "\n";
-    let output = input
-        .lines()
-        .map(|line| {
-            if checked {
-                options.indent_line(line) + sep
-            } else {
-                options.indent_line_unchecked(line) + sep
-            }
-        })
-        .collect::<String>();
-    output[..output.len() - 1].to_string()
+pub fn indent_str(input: &str, options: impl Into<CustomIndent>, checked: bool) -> String {
+    let options = options.into();
+    if input.contains(ansi::MARKER) {
+        return indent_ansi(input, options, "", checked);
+    }
+
+    let line_ending = options.options().line_endings();
+    let mut output = String::with_capacity(input.len() * 2);
+    for (i, line) in input.split_inclusive('\n').enumerate() {
+        let (text, ending) = match line.strip_suffix('\n') {
+            Some(text) => match text.strip_suffix('\r') {
+                Some(text) => (text, "\r\n"),
+                None => (text, "\n"),
+            },
+            None => (line, ""),
+        };
+
+        let indent = options.make_line_indent(i);
+        if options.should_indent(text) && !(checked && text.starts_with(&indent)) {
+            output.push_str(&indent);
+        }
+        output.push_str(text);
+        match line_ending {
+            Some(line_ending) if !ending.is_empty() => output.push_str(line_ending.as_str()),
+            _ => output.push_str(ending),
+        }
+    }
+    output
 }
 
 /// Second attempt at the `indent_str` function. This one is already looking much better, initial
@@ -43,36 +67,64 @@ pub fn indent_str(input: &str, options: IndentOptions, checked: bool) -> String
 /// original, but it seems to also be faster than the in-place version as well. Unsure of how that
 /// could possibly be the case?
 #[must_use]
-pub fn indent_str_v2(input: &str, options: IndentOptions) -> String {
+pub fn indent_str_v2(input: &str, options: impl Into<CustomIndent>) -> String {
+    let options = options.into();
+    if input.contains(ansi::MARKER) || !options.is_uniform() {
+        return indent_str_styled(input, options, "");
+    }
+    if let Some(line_ending) = options.options().line_endings() {
+        return indent_str_v2(&line_ending.convert(input), options.with_line_endings(None));
+    }
+
     let indent = options.make_indent();
     let mut output = String::with_capacity(input.len() * 2);
-    output.push_str(&indent);
+    // like the styled path, a line is only indented once it has a character (so a trailing newline
+    // isn't followed by an indent)
+    let mut line_start = true;
     for ch in input.chars() {
-        output.push(ch);
-        if ch == '\n' {
+        if line_start {
             output.push_str(&indent);
         }
+        output.push(ch);
+        line_start = ch == '\n';
     }
     output
 }
 
-/// Indent the given `input` string using the given [`options`](`IndentOptions`) in place.
+/// Indent the given `input` string using the given [`options`](`IndentOptions`) in place. The
+/// line endings are converted to [`IndentOptions::line_endings`] if they are set. If the input
+/// contains ansi escape sequences the indentation is isolated from their style (see
+/// [`indent_str_styled`]).
 ///
 /// # Examples
 /// ```
-/// # use reflors::indent::{indent_str, IndentOptions};
+/// # use reflors::indent::{indent_in_place, IndentOptions};
 /// let mut text = "One\nTwo\nThree".to_string();
 /// indent_in_place(&mut text, IndentOptions::one_tab());
 /// assert_eq!(text, "\tOne\n\tTwo\n\tThree");
 /// ```
-pub fn indent_in_place(input: &mut String, options: IndentOptions) {
+pub fn indent_in_place(input: &mut String, options: impl Into<CustomIndent>) {
+    let options = options.into();
+    if input.contains(ansi::MARKER) || !options.is_uniform() {
+        *input = indent_str_styled(input, options, "");
+        return;
+    }
+    if let Some(line_ending) = options.options().line_endings() {
+        *input = line_ending.convert(input);
+    }
+
+    if input.is_empty() {
+        return;
+    }
     let indent = options.make_indent();
     input.insert_str(0, indent.as_str());
     // let replacement = format!("\n{}", indent);
     // input.replace('\n', replacement.as_str());
+    // a trailing newline isn't followed by an indent, the same as in the styled path
     let breaks = input
         .match_indices('\n')
         .map(|(i, s)| i)
+        .filter(|&i| i + 1 < input.len())
         .rev()
         .collect::<Vec<_>>();
     input.reserve(breaks.len() * indent.len());
@@ -81,6 +133,46 @@ pub fn indent_in_place(input: &mut String, options: IndentOptions) {
     }
 }
 
+/// Indent the given `input` string using the given [`options`](`IndentOptions`), isolating the
+/// indentation from the style of the text: before each indent the active style is reset, the
+/// indent is written using the given `style` (which may be empty) and then the style is restored.
+/// This keeps background colors from bleeding into the indentation.
+///
+/// # Examples
+/// ```
+/// # use reflors::indent::{indent_str_styled, IndentOptions};
+/// let text = "\u{1b}[41mOne\nTwo\u{1b}[0m";
+/// assert_eq!(
+///     indent_str_styled(text, IndentOptions::two_spaces(), ""),
+///     "\u{1b}[41m\u{1b}[0m  \u{1b}[41mOne\n\u{1b}[0m  \u{1b}[41mTwo\u{1b}[0m"
+/// );
+/// assert_eq!(
+///     indent_str_styled("One", IndentOptions::two_spaces(), "\u{1b}[44m"),
+///     "\u{1b}[44m  \u{1b}[0mOne"
+/// );
+/// ```
+#[must_use]
+pub fn indent_str_styled(input: &str, options: impl Into<CustomIndent>, style: &str) -> String {
+    indent_ansi(input, options.into(), style, false)
+}
+
+/// Indents `input` through a [`Writer`] using the given `style`. If `checked` is true, lines whose
+/// visible text already starts with their indentation are left as they are.
+fn indent_ansi(input: &str, options: CustomIndent, style: &str, checked: bool) -> String {
+    let mut writer = Writer::new(options).with_style(style);
+    for (i, line) in input.split_inclusive('\n').enumerate() {
+        if checked && ansi::strip_ansi(line).starts_with(&writer.indent().make_line_indent(i)) {
+            writer.skip_line_indent();
+        }
+        // writing to a `Vec` can't fail
+        if writer.write_str(line).is_err() {
+            return String::new();
+        }
+    }
+    // the output is valid utf8 since the input is
+    writer.into_string().unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,6 +202,219 @@ mod tests {
         assert_eq!(text, "\tOne\n\tTwo\n\tThree");
     }
 
+    #[test]
+    fn writer_chunks() {
+        let mut writer = Writer::new(IndentOptions::two_spaces());
+        for chunk in ["On", "e\nTw", "o\n", "\nThree"] {
+            writer.write_str(chunk).unwrap();
+        }
+        assert!(!writer.at_line_start());
+        assert_eq!(writer.into_string().unwrap(), "  One\n  Two\n  \n  Three");
+
+        // characters split across writes are held back until they are complete
+        let text = "你好\nwörld";
+        for size in 1..text.len() {
+            let mut writer = Writer::new(IndentOptions::two_spaces());
+            for chunk in text.as_bytes().chunks(size) {
+                assert_eq!(writer.write_bytes(chunk).unwrap(), chunk.len());
+            }
+            assert_eq!(writer.into_string().unwrap(), "  你好\n  wörld");
+        }
+        assert!(Writer::new(IndentOptions::two_spaces())
+            .write_bytes(b"ab\xffcd")
+            .is_err());
+    }
+
+    #[test]
+    fn writer_io() {
+        use std::io::Write;
+
+        let mut writer = Writer::new(IndentOptions::one_tab());
+        writeln!(writer, "\u{1b}[31mred\u{1b}[0m").unwrap();
+        writer.write_all("plain".as_bytes()).unwrap();
+        assert_eq!(
+            writer.into_string().unwrap(),
+            "\u{1b}[31m\u{1b}[0m\t\u{1b}[31mred\u{1b}[0m\n\tplain"
+        );
+    }
+
+    #[test]
+    fn line_endings() {
+        let text = "One\r\nTwo\r\n\r\nThree";
+        let crlf = "  One\r\n  Two\r\n  \r\n  Three";
+        let lf = "  One\n  Two\n  \n  Three";
+        let options = IndentOptions::two_spaces();
+        let to_lf = options.with_line_endings(Some(LineEnding::posix()));
+        assert_eq!(indent_str(text, options, false), crlf);
+        assert_eq!(indent_str(text, to_lf, false), lf);
+        assert_eq!(indent_str_v2(text, options), crlf);
+        assert_eq!(indent_str_v2(text, to_lf), lf);
+        assert_eq!(
+            indent_str_v2(
+                "One\nTwo",
+                options.with_line_endings(Some(LineEnding::windows()))
+            ),
+            "  One\r\n  Two"
+        );
+
+        let mut in_place = text.to_string();
+        indent_in_place(&mut in_place, to_lf);
+        assert_eq!(in_place, lf);
+
+        // the writer converts line endings even when they are split across writes
+        let mut writer = Writer::new(to_lf);
+        for chunk in ["One\r", "\nTwo\r", "\r\n\u{1b}[1mThree\r"] {
+            writer.write_str(chunk).unwrap();
+        }
+        assert_eq!(
+            writer.into_string().unwrap(),
+            "  One\n  Two\r\n\u{1b}[1m\u{1b}[0m  \u{1b}[1mThree\r"
+        );
+        assert_eq!(indent_str("", options, false), "");
+    }
+
+    #[test]
+    fn trailing_newline() {
+        let options = IndentOptions::two_spaces();
+        assert_eq!(indent_str("a\nb\n", options, false), "  a\n  b\n");
+        assert_eq!(indent_str("a\r\n\n", options, false), "  a\r\n  \n");
+        assert_eq!(
+            indent_str(
+                "a\r\nb\r\n",
+                options.with_line_endings(Some(LineEnding::posix())),
+                false
+            ),
+            "  a\n  b\n"
+        );
+
+        // plain and styled text are indented the same way
+        for text in ["a\nb\n", "a\r\n\nb", "a\n\n", "\n"] {
+            let styled = format!("\u{1b}[1m\u{1b}[0m{text}");
+            assert_eq!(
+                indent_str(&styled, options, false),
+                format!("\u{1b}[1m\u{1b}[0m{}", indent_str(text, options, false)),
+                "{text:?}"
+            );
+
+            // every entry point agrees with `indent_str`
+            let expected = indent_str(text, options, false);
+            assert_eq!(indent_str_v2(text, options), expected, "{text:?}");
+            assert_eq!(
+                indent_str_v2(&styled, options),
+                format!("\u{1b}[1m\u{1b}[0m{expected}"),
+                "{text:?}"
+            );
+            let mut in_place = text.to_string();
+            indent_in_place(&mut in_place, options);
+            assert_eq!(in_place, expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn custom_indents() {
+        let text = "One\nTwo\n\nThree";
+        assert_eq!(
+            indent_str(text, IndentOptions::custom("│ "), false),
+            "│ One\n│ Two\n│ \n│ Three"
+        );
+        assert_eq!(
+            indent_str(text, IndentOptions::tabs(2).with_prefix("> "), false),
+            "> > One\n> > Two\n> > \n> > Three"
+        );
+        assert_eq!(
+            indent_str(text, IndentOptions::hanging(2), false),
+            "One\n  Two\n  \n  Three"
+        );
+        assert_eq!(
+            indent_str_v2(text, IndentOptions::paragraph(4)),
+            "    One\nTwo\n\nThree"
+        );
+        assert_eq!(
+            indent_str_v2(text, IndentOptions::spaces(2).with_first_line("- ")),
+            "- One\n  Two\n  \n  Three"
+        );
+
+        let skip_blank = IndentOptions::custom("> ").with_predicate(|line| !line.trim().is_empty());
+        assert_eq!(
+            indent_str(text, skip_blank.clone(), false),
+            "> One\n> Two\n\n> Three"
+        );
+        let mut in_place = "One\r\n\r\nTwo\r\n".to_string();
+        indent_in_place(&mut in_place, skip_blank);
+        assert_eq!(in_place, "> One\r\n\r\n> Two\r\n");
+
+        // a per-line indent handles a trailing newline the same way as a uniform one
+        let text = "One\nTwo\n";
+        let uniform = indent_str_v2(text, IndentOptions::two_spaces());
+        let per_line = IndentOptions::spaces(2).with_first_line("  ");
+        assert!(!per_line.is_uniform());
+        assert_eq!(indent_str_v2(text, per_line.clone()), uniform);
+        assert_eq!(indent_str(text, per_line.clone(), false), uniform);
+        let mut in_place = text.to_string();
+        indent_in_place(&mut in_place, per_line);
+        assert_eq!(in_place, uniform);
+        assert_eq!(
+            indent_str_v2(text, IndentOptions::hanging(2)),
+            "One\n  Two\n"
+        );
+
+        // a styled gutter keeps its own style
+        let gutter =
+            IndentOptions::custom("\u{1b}[2m│\u{1b}[0m ").with_predicate(|line| line != "skip");
+        let mut writer = Writer::new(gutter);
+        for chunk in ["\u{1b}[1mbo", "ld\u{1b}[0m\nsk", "ip\nplain"] {
+            writer.write_str(chunk).unwrap();
+        }
+        assert_eq!(
+            writer.into_string().unwrap(),
+            "\u{1b}[1m\u{1b}[0m\u{1b}[2m│\u{1b}[0m \u{1b}[1mbold\u{1b}[0m\nskip\n\u{1b}[2m│\u{1b}[0m plain"
+        );
+    }
+
+    #[test]
+    fn style_isolation() {
+        let text = "\u{1b}[44mblue\nstill blue\u{1b}[0m\nplain";
+        let expected = "\u{1b}[44m\u{1b}[0m  \u{1b}[44mblue\n\u{1b}[0m  \u{1b}[44mstill blue\u{1b}[0m\n  plain";
+        assert_eq!(
+            indent_str(text, IndentOptions::two_spaces(), false),
+            expected
+        );
+        assert_eq!(indent_str_v2(text, IndentOptions::two_spaces()), expected);
+        let mut in_place = text.to_string();
+        indent_in_place(&mut in_place, IndentOptions::two_spaces());
+        assert_eq!(in_place, expected);
+
+        // lines that are already indented are only skipped when checked, escapes included
+        let text = "  \u{1b}[44mblue\n\u{1b}[44m  blue\u{1b}[0m\nplain";
+        assert_eq!(
+            indent_str(text, IndentOptions::two_spaces(), true),
+            "  \u{1b}[44mblue\n\u{1b}[44m  blue\u{1b}[0m\n  plain"
+        );
+        assert_eq!(
+            indent_str(text, IndentOptions::two_spaces(), false),
+            "    \u{1b}[44mblue\n\u{1b}[44m\u{1b}[0m  \u{1b}[44m\u{1b}[44m  blue\u{1b}[0m\n  plain"
+        );
+
+        // the indent can have its own style, which doesn't leak into the text
+        assert_eq!(
+            indent_str_styled("a\n\u{1b}[1mb", IndentOptions::spaces(1), "\u{1b}[7m"),
+            "\u{1b}[7m \u{1b}[0ma\n\u{1b}[1m\u{1b}[0m\u{1b}[7m \u{1b}[0m\u{1b}[1mb"
+        );
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(
+            indent_str(
+                &format!("see {link}\nand {link}"),
+                IndentOptions::two_spaces(),
+                false
+            ),
+            format!("  see {link}\n  and {link}")
+        );
+    }
+
     /// Initial testing gives these results:
     ///
     /// Using 100000 iterations...
@@ -4,6 +4,11 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    sync::Arc,
+};
+
 /// How text can be indented, spaces (correct) vs tabs (incorrect).
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum LineEnding {
@@ -36,6 +41,29 @@ impl LineEnding {
         }
     }
 
+    /// Converts every line ending in `input` (either `\n` or `\r\n`) to this [`LineEnding`].
+    ///
+    /// # Examples
+    /// ```
+    /// # use reflors::indent::LineEnding;
+    /// assert_eq!(LineEnding::windows().convert("a\nb\r\nc"), "a\r\nb\r\nc");
+    /// assert_eq!(LineEnding::posix().convert("a\nb\r\nc\r"), "a\nb\nc\r");
+    /// ```
+    #[must_use]
+    pub fn convert(&self, input: &str) -> String {
+        let mut output = String::with_capacity(input.len());
+        for line in input.split_inclusive('\n') {
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    output.push_str(line.strip_suffix('\r').unwrap_or(line));
+                    output.push_str(self.as_str());
+                }
+                None => output.push_str(line),
+            }
+        }
+        output
+    }
+
     /// Get a string containing the actual [`LineEnding`].
     #[must_use]
     pub const fn as_str(&self) -> &'static str {
@@ -88,13 +116,19 @@ impl IndentStyle {
 }
 
 /// Options for determining how text should be indented.
+///
+/// Every line is indented by `number` spaces or tabs. For an arbitrary
+/// [prefix](`IndentOptions::with_prefix`), a separate [first line](`IndentOptions::with_first_line`)
+/// indentation or a [predicate](`IndentOptions::with_predicate`) choosing which lines get indented
+/// at all, see [`CustomIndent`].
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct IndentOptions {
     /// Whether to indent text with spaces or tabs.
     style: IndentStyle,
     /// How much should text be indented.
     number: usize,
-    /// The type of line ending the output should be rejoined with. If none, they will be detected from the input.
+    /// The type of line ending the output should be rejoined with. If none, the line endings of the
+    /// input are kept as they are.
     line_endings: Option<LineEnding>,
 }
 
@@ -131,6 +165,27 @@ impl IndentOptions {
     pub const fn one_tab() -> Self {
         Self::tabs(1)
     }
+
+    /// Creates a [`CustomIndent`] that prefixes every line with the given string, e.g. `"│ "`
+    /// or `"> "`. The prefix may contain ansi escape sequences to style it.
+    #[must_use]
+    pub fn custom(prefix: &str) -> CustomIndent {
+        Self::spaces(1).with_prefix(prefix)
+    }
+
+    /// Creates a [`CustomIndent`] representing a hanging indent, where every line **except** the
+    /// first is indented by `number` spaces.
+    #[must_use]
+    pub fn hanging(number: usize) -> CustomIndent {
+        Self::spaces(number).with_first_line("")
+    }
+
+    /// Creates a [`CustomIndent`] representing a paragraph indent, where **only** the first line
+    /// is indented by `number` spaces.
+    #[must_use]
+    pub fn paragraph(number: usize) -> CustomIndent {
+        Self::spaces(0).with_first_line(&" ".repeat(number))
+    }
 }
 
 impl IndentOptions {
@@ -145,24 +200,50 @@ impl IndentOptions {
         }
     }
 
-    /// Create an [`IndentOptions`] using spaces, auto-detected line endings, and the given indentation amount.
+    /// Create an [`IndentOptions`] using spaces, unchanged line endings, and the given indentation amount.
     #[must_use]
     pub const fn spaces(number: usize) -> Self {
-        Self {
-            style: IndentStyle::Spaces,
-            number,
-            line_endings: None,
-        }
+        Self::new(IndentStyle::Spaces, number, None)
     }
 
-    /// Create an [`IndentOptions`] using tabs, auto-detected line endings, and the given indentation amount.
+    /// Create an [`IndentOptions`] using tabs, unchanged line endings, and the given indentation amount.
     #[must_use]
     pub const fn tabs(number: usize) -> Self {
-        Self {
-            style: IndentStyle::Tabs,
-            number,
-            line_endings: None,
-        }
+        Self::new(IndentStyle::Tabs, number, None)
+    }
+}
+
+impl IndentOptions {
+    /// Sets the line endings the output should be joined with, or `None` to keep the line endings
+    /// of the input as they are.
+    #[must_use]
+    pub const fn with_line_endings(mut self, line_endings: Option<LineEnding>) -> Self {
+        self.line_endings = line_endings;
+        self
+    }
+
+    /// Creates a [`CustomIndent`] using these options, with a custom prefix that is used
+    /// (repeated [`IndentOptions::number`] times) instead of spaces or tabs.
+    #[must_use]
+    pub fn with_prefix(self, prefix: &str) -> CustomIndent {
+        CustomIndent::from(self).with_prefix(prefix)
+    }
+
+    /// Creates a [`CustomIndent`] using these options for every line but the first, which is
+    /// indented with the given string as-is. An empty string gives a hanging indent.
+    #[must_use]
+    pub fn with_first_line(self, indent: &str) -> CustomIndent {
+        CustomIndent::from(self).with_first_line(indent)
+    }
+
+    /// Creates a [`CustomIndent`] using these options, which only indents the lines the predicate
+    /// accepts. See [`CustomIndent::with_predicate`].
+    #[must_use]
+    pub fn with_predicate(
+        self,
+        predicate: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> CustomIndent {
+        CustomIndent::from(self).with_predicate(predicate)
     }
 }
 
@@ -222,3 +303,149 @@ impl IndentOptions {
         format!("{}{}", indent, input)
     }
 }
+
+/// Indentation that goes beyond [`IndentOptions`]: an arbitrary prefix instead of spaces or tabs,
+/// a separate indentation for the first line (for hanging and paragraph indents) and a predicate
+/// choosing which lines get indented at all. Every function taking [`IndentOptions`] accepts a
+/// [`CustomIndent`] too.
+///
+/// # Examples
+/// ```
+/// # use reflors::indent::{indent_str, CustomIndent, IndentOptions};
+/// let quote = IndentOptions::custom("> ").with_first_line(">> ");
+/// assert_eq!(indent_str("One\nTwo", quote, false), ">> One\n> Two");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CustomIndent {
+    /// The style, level and line endings of the indentation.
+    options: IndentOptions,
+    /// A custom prefix that is repeated `number` times instead of the `style`.
+    prefix: Option<String>,
+    /// The indentation of the first line, if it differs from the rest.
+    first_line: Option<String>,
+    /// Decides which lines get indented, if not all of them.
+    predicate: Option<Predicate>,
+}
+
+/// A predicate deciding which lines get indented.
+#[derive(Clone)]
+struct Predicate(Arc<dyn Fn(&str) -> bool + Send + Sync>);
+
+impl Debug for Predicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Predicate").finish_non_exhaustive()
+    }
+}
+
+impl From<IndentOptions> for CustomIndent {
+    fn from(options: IndentOptions) -> Self {
+        Self {
+            options,
+            prefix: None,
+            first_line: None,
+            predicate: None,
+        }
+    }
+}
+
+impl CustomIndent {
+    /// Sets the line endings the output should be joined with, or `None` to keep the line endings
+    /// of the input as they are.
+    #[must_use]
+    pub const fn with_line_endings(mut self, line_endings: Option<LineEnding>) -> Self {
+        self.options = self.options.with_line_endings(line_endings);
+        self
+    }
+
+    /// Sets a custom prefix that is used (repeated [`IndentOptions::number`] times) instead of
+    /// spaces or tabs.
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Sets the indentation of the first line, which is used as-is instead of the regular
+    /// indentation. An empty string gives a hanging indent.
+    #[must_use]
+    pub fn with_first_line(mut self, indent: &str) -> Self {
+        self.first_line = Some(indent.to_string());
+        self
+    }
+
+    /// Sets a predicate that decides which lines get indented (given the line without its line
+    /// ending), similar to the `predicate` of python's `textwrap.indent`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use reflors::indent::{indent_str, IndentOptions};
+    /// let skip_blank = IndentOptions::two_spaces().with_predicate(|line| !line.trim().is_empty());
+    /// assert_eq!(indent_str("One\n\nTwo", skip_blank, false), "  One\n\n  Two");
+    /// ```
+    #[must_use]
+    pub fn with_predicate(
+        mut self,
+        predicate: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.predicate = Some(Predicate(Arc::new(predicate)));
+        self
+    }
+}
+
+impl CustomIndent {
+    /// Gets the [`IndentOptions`] used for the style, level and line endings of the indentation.
+    #[must_use]
+    pub const fn options(&self) -> &IndentOptions {
+        &self.options
+    }
+
+    /// Gets the custom prefix used instead of spaces or tabs, if any.
+    #[must_use]
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Gets the indentation of the first line, if it differs from the rest.
+    #[must_use]
+    pub fn first_line(&self) -> Option<&str> {
+        self.first_line.as_deref()
+    }
+
+    /// Gets the predicate that decides which lines get indented, if any.
+    #[must_use]
+    pub fn predicate(&self) -> Option<&(dyn Fn(&str) -> bool + Send + Sync)> {
+        self.predicate.as_ref().map(|predicate| &*predicate.0)
+    }
+
+    /// Whether every line gets the same indentation, i.e. there is no separate first-line
+    /// indentation and no predicate.
+    #[must_use]
+    pub const fn is_uniform(&self) -> bool {
+        self.first_line.is_none() && self.predicate.is_none()
+    }
+
+    /// Checks whether the given line (without its line ending) should be indented.
+    #[must_use]
+    pub fn should_indent(&self, line: &str) -> bool {
+        self.predicate().is_none_or(|predicate| predicate(line))
+    }
+
+    /// Creates a [`String`] representing the full indentation.
+    #[must_use]
+    pub fn make_indent(&self) -> String {
+        match &self.prefix {
+            Some(prefix) => prefix.repeat(self.options.number()),
+            None => self.options.make_indent(),
+        }
+    }
+
+    /// Creates a [`String`] representing the full indentation of the line with the given (zero
+    /// based) `index`.
+    #[must_use]
+    pub fn make_line_indent(&self, index: usize) -> String {
+        match &self.first_line {
+            Some(first_line) if index == 0 => first_line.clone(),
+            _ => self.make_indent(),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io::{Result as IoResult, Write as IoWrite};
+
+use crate::{
+    ansi::{self, writer::Writer as AnsiWriter},
+    fmt::Utf8Decoder,
+    iter::{EscapeBuffer, Token, Tokens},
+    pipeline::{self, Reflow},
+    Error, Result,
+};
+
+use super::{CustomIndent, IndentOptions};
+
+/// A streaming, ansi-aware indenting writer. Each line is prefixed with the indentation described
+/// by its [`IndentOptions`] (or [`CustomIndent`]) as it is written, and whether the writer is at
+/// the start of a line is tracked across writes, so input can be written in arbitrarily sized
+/// chunks.
+///
+/// The indentation is inserted right before the first **visible** character of each line, and is
+/// isolated from the style of the surrounding text: the active style is reset before the indent,
+/// the indent is written (with its own [style](`Writer::with_style`), if any) and then the style
+/// is restored, so colors don't bleed into the indentation.
+///
+/// If [`IndentOptions::line_endings`] is set every line ending is converted to it, otherwise line
+/// endings are written as they are. If [`CustomIndent::predicate`] is set each line is buffered
+/// until it is complete, so the predicate can decide whether it gets indented.
+///
+/// Source: [muesli/reflow/indent/indent.go](https://github.com/muesli/reflow/blob/master/indent/indent.go)
+pub struct Writer<W> {
+    indent: CustomIndent,
+    style: String,
+    inner: AnsiWriter<W>,
+    line: String,
+    line_index: usize,
+    skip_indent: bool,
+    escapes: EscapeBuffer,
+    pending_cr: bool,
+    decoder: Utf8Decoder,
+}
+
+impl Writer<Vec<u8>> {
+    /// Creates a new [`Writer`] using the given [`IndentOptions`] (or [`CustomIndent`]) and an
+    /// internal buffer.
+    #[must_use]
+    pub fn new(options: impl Into<CustomIndent>) -> Self {
+        Self::new_piped(options, Vec::new())
+    }
+
+    /// **Consumes** this [`Writer`] and tries to create a string from its internal buffer, after
+    /// [closing](`Writer::close`) it.
+    ///
+    /// ## Errors
+    /// - `crate::Error::Utf8` if the buffer is not a valid utf8 string
+    /// - `crate::Error::Io` if writing the last pending character fails
+    pub fn into_string(mut self) -> Result<String> {
+        self.close()?;
+        self.inner.into_string()
+    }
+}
+
+impl<W> Writer<W> {
+    /// Creates a new [`Writer`] using the given [`IndentOptions`] that writes to `writer`, which
+    /// can be any [`std::io::Write`] (or any [`std::fmt::Write`] through a
+    /// [`FmtWriter`](`crate::fmt::FmtWriter`)).
+    #[must_use]
+    pub fn new_piped(options: impl Into<CustomIndent>, writer: W) -> Self {
+        Self {
+            indent: options.into(),
+            style: String::new(),
+            inner: AnsiWriter::new(writer),
+            line: String::new(),
+            line_index: 0,
+            skip_indent: false,
+            escapes: EscapeBuffer::default(),
+            pending_cr: false,
+            decoder: Utf8Decoder::default(),
+        }
+    }
+
+    /// Sets the ansi escape sequence(s) used to style the indentation itself, e.g. to give it a
+    /// background color.
+    #[must_use]
+    pub fn with_style(mut self, style: &str) -> Self {
+        self.style = style.to_string();
+        self
+    }
+
+    /// Gets the ansi escape sequence(s) used to style the indentation itself.
+    #[must_use]
+    pub fn style(&self) -> &str {
+        self.style.as_str()
+    }
+
+    /// Gets the [`IndentOptions`] used by this [`Writer`].
+    #[must_use]
+    pub fn options(&self) -> &IndentOptions {
+        self.indent.options()
+    }
+
+    /// Gets the [`CustomIndent`] used by this [`Writer`], which is just its [`IndentOptions`] if
+    /// it was created with those.
+    #[must_use]
+    pub fn indent(&self) -> &CustomIndent {
+        &self.indent
+    }
+
+    /// **Consumes** this [`Writer`] and returns the underlying writer, without
+    /// [closing](`Writer::close`) it.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner()
+    }
+
+    /// Whether the next visible character written will start a new (indented) line.
+    #[must_use]
+    pub fn at_line_start(&self) -> bool {
+        !self.skip_indent
+    }
+
+    /// Leaves the rest of the current line unindented.
+    pub(crate) fn skip_line_indent(&mut self) {
+        self.skip_indent = true;
+    }
+}
+
+impl<W: IoWrite> Writer<W> {
+    /// Write the given bytes to this [`Writer`]. A utf8 character may be split across writes, its
+    /// start is held back until the rest of it is written.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If `bytes` can't be valid utf8
+    /// - `Error::Io` - If writing to the underlying buffer fails
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
+        let s = self.decoder.decode(bytes)?;
+        self.write_str(&s)?;
+        Ok(bytes.len())
+    }
+
+    /// Write the given string to this [`Writer`].
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying buffer fails
+    pub fn write_str(&mut self, s: &str) -> Result<usize> {
+        if self.indent.predicate().is_some() {
+            for ch in s.chars() {
+                self.line.push(ch);
+                if ch == '\n' {
+                    self.write_line()?;
+                }
+            }
+        } else {
+            self.write_chars(s)?;
+        }
+
+        Ok(s.len())
+    }
+
+    /// Finishes writing by writing any line or carriage return that is still being held back, and
+    /// flushes the underlying writer.
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying buffer fails
+    pub fn close(&mut self) -> Result<()> {
+        if !self.line.is_empty() {
+            self.write_line()?;
+        }
+        if std::mem::take(&mut self.pending_cr) {
+            self.write_visible("\r")?;
+        }
+
+        self.inner.flush_writer()
+    }
+
+    /// Writes the buffered line, only indenting it if the predicate allows it.
+    fn write_line(&mut self) -> Result<()> {
+        let line = std::mem::take(&mut self.line);
+        let content = line.strip_suffix('\n').unwrap_or(&line);
+        if !self
+            .indent
+            .should_indent(content.strip_suffix('\r').unwrap_or(content))
+        {
+            self.skip_indent = true;
+        }
+
+        self.write_chars(&line)
+    }
+
+    fn write_chars(&mut self, s: &str) -> Result<()> {
+        let line_ending = self.options().line_endings();
+        let mut char_buffer = [0; 4];
+
+        let input = self.escapes.complete(s);
+        for token in Tokens::new(&input) {
+            let text = match token {
+                Token::Text(text) => text,
+                Token::Escape { raw, .. } => {
+                    if std::mem::take(&mut self.pending_cr) {
+                        self.write_visible("\r")?;
+                    }
+                    self.inner.write_str(raw)?;
+                    continue;
+                }
+            };
+
+            for ch in text.chars() {
+                // a carriage return is only held back until we know whether it is part of a line
+                // ending
+                if std::mem::take(&mut self.pending_cr) {
+                    if ch == '\n' {
+                        self.write_newline()?;
+                        continue;
+                    }
+                    self.write_visible("\r")?;
+                }
+
+                if ch == '\r' && line_ending.is_some() {
+                    self.pending_cr = true;
+                } else if ch == '\n' {
+                    self.write_newline()?;
+                } else {
+                    self.write_visible(ch.encode_utf8(&mut char_buffer))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a visible piece of text, indenting it first if it starts a line.
+    fn write_visible(&mut self, s: &str) -> Result<()> {
+        if !self.skip_indent {
+            self.write_indent()?;
+            self.skip_indent = true;
+        }
+
+        self.inner.write_str(s)?;
+        Ok(())
+    }
+
+    /// Writes a line ending (converted to [`IndentOptions::line_endings`] if set).
+    fn write_newline(&mut self) -> Result<()> {
+        let line_ending = self.options().line_endings().map_or("\n", |le| le.as_str());
+        self.write_visible(line_ending)?;
+        self.skip_indent = false;
+        self.line_index += 1;
+        Ok(())
+    }
+
+    fn write_indent(&mut self) -> Result<()> {
+        let indent = self.indent.make_line_indent(self.line_index);
+        if indent.is_empty() {
+            return Ok(());
+        }
+
+        self.inner.reset_ansi()?;
+        if self.style.is_empty() {
+            self.inner.write_raw(indent.as_bytes())?;
+        } else {
+            self.inner.write_raw(self.style.as_bytes())?;
+            self.inner.write_raw(indent.as_bytes())?;
+            self.inner.write_raw(b"\x1b[0m")?;
+        }
+        self.inner.restore_ansi()
+    }
+}
+
+impl Reflow for Writer<Vec<u8>> {
+    fn write(&mut self, input: &str, out: &mut String) -> Result<()> {
+        self.write_str(input)?;
+        pipeline::drain(self.inner.get_mut(), out)
+    }
+
+    fn finish(&mut self, out: &mut String) -> Result<()> {
+        self.close()?;
+        pipeline::drain(self.inner.get_mut(), out)?;
+        *self = Writer::new(self.indent.clone()).with_style(&self.style);
+        Ok(())
+    }
+}
+
+impl<W: IoWrite> IoWrite for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.write_bytes(buf).map_err(Into::into)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush_writer().map_err(Into::into)
+    }
+}
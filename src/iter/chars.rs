@@ -0,0 +1,194 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::sync::Arc;
+
+use crate::ansi;
+
+use super::{Token, Tokens};
+
+/// A character yielded by [`VisibleChars`], along with where it is and how it looks.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VisibleChar {
+    /// The character itself.
+    pub ch: char,
+    /// The byte offset of the character in the input.
+    pub offset: usize,
+    /// The **visible** column the character starts at.
+    pub column: usize,
+    /// The number of columns the character takes up (see [`ansi::char_width_at`]), tabs take up
+    /// the columns until the next tab stop.
+    pub width: usize,
+    /// The SGR escape sequences (colors, bold, ...) set since the last reset, which style the
+    /// character. Empty if it isn't styled.
+    pub style: Arc<str>,
+}
+
+/// An iterator over every character in a string that isn't part of an ansi escape sequence,
+/// yielding a [`VisibleChar`] with its byte offset, column, width and active style.
+///
+/// Unlike [`Viserator`](`super::Viserator`) this also yields zero-width characters (with a width
+/// of zero), so every character can be mapped back to the input. Columns are counted the same way
+/// as [`ansi::visible_width`] does: they restart at zero after a `\n` and tabs expand to the next
+/// multiple of 8.
+///
+/// # Examples
+/// ```
+/// # use reflors::iter::VisibleChars;
+/// let chars = VisibleChars::new("a\x1b[1m東b").collect::<Vec<_>>();
+/// assert_eq!(chars[1].ch, '東');
+/// assert_eq!((chars[1].offset, chars[1].column, chars[1].width), (5, 1, 2));
+/// assert_eq!(&*chars[1].style, "\x1b[1m");
+/// assert_eq!((chars[2].offset, chars[2].column), (8, 3));
+/// ```
+pub struct VisibleChars<'a> {
+    input: &'a str,
+    tokens: Tokens<'a>,
+    /// The characters of the current run of text, and its byte offset in the input.
+    text: std::str::CharIndices<'a>,
+    text_start: usize,
+    column: usize,
+    styles: String,
+    style: Arc<str>,
+}
+
+impl<'a> VisibleChars<'a> {
+    /// Creates a new [`VisibleChars`] iterator over the given `input`.
+    #[must_use]
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            tokens: Tokens::new(input),
+            text: "".char_indices(),
+            text_start: 0,
+            column: 0,
+            styles: String::new(),
+            style: Arc::from(""),
+        }
+    }
+
+    /// Keeps track of the style set by the given escape sequence, if it is an SGR sequence.
+    fn apply(&mut self, sequence: Token<'_>) {
+        if !sequence.is_sgr() {
+            return;
+        }
+
+        if sequence.is_reset() {
+            self.styles.clear();
+        } else {
+            self.styles.push_str(sequence.as_str());
+        }
+        self.style = Arc::from(self.styles.as_str());
+    }
+}
+
+impl Iterator for VisibleChars<'_> {
+    type Item = VisibleChar;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((offset, ch)) = self.text.next() {
+                let column = self.column;
+                let width = ansi::char_width_at(ch, column);
+                self.column = if ch == '\n' { 0 } else { column + width };
+
+                return Some(VisibleChar {
+                    ch,
+                    offset: self.text_start + offset,
+                    column,
+                    width,
+                    style: Arc::clone(&self.style),
+                });
+            }
+
+            let start = self.input.len() - self.tokens.remainder().len();
+            match self.tokens.next()? {
+                Token::Text(text) => {
+                    self.text = text.char_indices();
+                    self.text_start = start;
+                }
+                escape @ Token::Escape { .. } => self.apply(escape),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn columns(input: &str) -> Vec<(char, usize, usize, usize)> {
+        VisibleChars::new(input)
+            .map(|vis| (vis.ch, vis.offset, vis.column, vis.width))
+            .collect()
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(
+            columns("ab\tc\nd"),
+            vec![
+                ('a', 0, 0, 1),
+                ('b', 1, 1, 1),
+                ('\t', 2, 2, 6),
+                ('c', 3, 8, 1),
+                ('\n', 4, 9, 0),
+                ('d', 5, 0, 1),
+            ]
+        );
+        assert_eq!(
+            columns("東e\u{301}\x1b[31m!"),
+            vec![
+                ('東', 0, 0, 2),
+                ('e', 3, 2, 1),
+                ('\u{301}', 4, 3, 0),
+                ('!', 11, 3, 1)
+            ]
+        );
+        assert_eq!(columns("\x1b[1m\x1b[0m"), vec![]);
+    }
+
+    #[test]
+    fn tabs() {
+        assert_eq!(columns("\tx"), vec![('\t', 0, 0, 8), ('x', 1, 8, 1)]);
+        assert_eq!(
+            columns("\x1b[1mabc\tx"),
+            vec![
+                ('a', 4, 0, 1),
+                ('b', 5, 1, 1),
+                ('c', 6, 2, 1),
+                ('\t', 7, 3, 5),
+                ('x', 8, 8, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(
+            columns(link),
+            vec![
+                ('l', 26, 0, 1),
+                ('i', 27, 1, 1),
+                ('n', 28, 2, 1),
+                ('k', 29, 3, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn styles() {
+        let styles = VisibleChars::new("a\x1b[1mb\x1b[31mc\x1b[0md\x1b[2Ke\x1b[4mf\x1b[mg")
+            .map(|vis| vis.style.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            styles,
+            vec!["", "\x1b[1m", "\x1b[1m\x1b[31m", "", "", "\x1b[4m", ""]
+        );
+    }
+}
@@ -0,0 +1,548 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Line break opportunities as described by the
+//! [Unicode Line Breaking Algorithm (UAX #14)](https://www.unicode.org/reports/tr14/).
+//!
+//! The class table below is not generated from the full `LineBreak.txt` property data, it covers
+//! the ranges that matter for terminal text (ascii punctuation, the common unicode spaces, joiners
+//! and dashes, CJK punctuation and ideographs, emoji and regional indicators) and falls back to
+//! [`LineBreakClass::AL`] for everything else. Hangul syllables and jamo are treated as ideographs
+//! and complex-context (south east asian) scripts are treated as alphabetic, as allowed by LB1.
+
+use super::{Token, Tokens};
+
+/// The line breaking classes from UAX #14 that are used by [`LineBreaks`].
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LineBreakClass {
+    /// Mandatory break (form feed, vertical tab, line and paragraph separators).
+    BK,
+    /// Carriage return.
+    CR,
+    /// Line feed.
+    LF,
+    /// Next line (U+0085).
+    NL,
+    /// Space.
+    SP,
+    /// Zero width space (U+200B), always allows a break after it.
+    ZW,
+    /// Word joiner (U+2060 and U+FEFF), prohibits breaks on either side.
+    WJ,
+    /// Non-breaking glue (U+00A0 and friends), prohibits breaks on either side.
+    GL,
+    /// Zero width joiner.
+    ZWJ,
+    /// Combining marks and control characters.
+    CM,
+    /// Opening punctuation.
+    OP,
+    /// Closing punctuation.
+    CL,
+    /// Closing parenthesis.
+    CP,
+    /// Ambiguous quotation marks.
+    QU,
+    /// Exclamation and interrogation.
+    EX,
+    /// Infix numeric separators.
+    IS,
+    /// Symbols allowing a break after them (`/`).
+    SY,
+    /// Numeric digits.
+    NU,
+    /// Numeric prefixes (currency symbols, `+`).
+    PR,
+    /// Numeric postfixes (`%`, degree signs).
+    PO,
+    /// Break opportunity after (most dashes and non-joining spaces).
+    BA,
+    /// Break opportunity before.
+    BB,
+    /// Break opportunity before and after (em dash).
+    B2,
+    /// Hyphen-minus.
+    HY,
+    /// Non-starters (small kana, iteration marks).
+    NS,
+    /// Inseparable characters (ellipses).
+    IN,
+    /// Ideographic characters.
+    ID,
+    /// Regional indicators.
+    RI,
+    /// Alphabetic characters and everything not otherwise classified.
+    AL,
+}
+
+impl LineBreakClass {
+    /// Gets the [`LineBreakClass`] of the given character.
+    ///
+    /// # Examples
+    /// ```
+    /// # use reflors::iter::LineBreakClass;
+    /// assert_eq!(LineBreakClass::of('a'), LineBreakClass::AL);
+    /// assert_eq!(LineBreakClass::of('\u{a0}'), LineBreakClass::GL);
+    /// assert_eq!(LineBreakClass::of('東'), LineBreakClass::ID);
+    /// ```
+    #[must_use]
+    #[allow(clippy::match_same_arms, clippy::too_many_lines)]
+    pub fn of(ch: char) -> Self {
+        use LineBreakClass::{
+            AL, B2, BA, BB, BK, CL, CM, CP, CR, EX, GL, HY, ID, IN, IS, LF, NL, NS, NU, OP, PO, PR,
+            QU, RI, SP, SY, WJ, ZW, ZWJ,
+        };
+
+        match ch {
+            '\u{0B}' | '\u{0C}' | '\u{2028}' | '\u{2029}' => BK,
+            '\r' => CR,
+            '\n' => LF,
+            '\u{85}' => NL,
+            ' ' => SP,
+            '\u{200B}' => ZW,
+            '\u{2060}' | '\u{FEFF}' => WJ,
+            '\u{A0}' | '\u{202F}' | '\u{180E}' | '\u{034F}' | '\u{2007}' | '\u{2011}'
+            | '\u{0F08}' | '\u{0F0C}' | '\u{0F12}' => GL,
+            '\u{200D}' => ZWJ,
+            '\t'
+            | '|'
+            | '\u{AD}'
+            | '\u{1680}'
+            | '\u{2000}'..='\u{2006}'
+            | '\u{2008}'..='\u{200A}'
+            | '\u{2010}'
+            | '\u{2012}'
+            | '\u{2013}'
+            | '\u{205F}'
+            | '\u{3000}' => BA,
+            '\u{00}'..='\u{1F}'
+            | '\u{7F}'..='\u{9F}'
+            | '\u{200C}'
+            | '\u{0300}'..='\u{036F}'
+            | '\u{0483}'..='\u{0489}'
+            | '\u{0591}'..='\u{05BD}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{FE20}'..='\u{FE2F}'
+            | '\u{1F3FB}'..='\u{1F3FF}'
+            | '\u{E0020}'..='\u{E007F}'
+            | '\u{E0100}'..='\u{E01EF}' => CM,
+            '-' => HY,
+            '\u{B4}' | '\u{2C8}' | '\u{2CC}' | '\u{2DF}' => BB,
+            '\u{2014}' => B2,
+            '(' | '[' | '{' | '\u{A1}' | '\u{BF}' | '\u{201A}' | '\u{201E}' | '\u{2045}'
+            | '\u{207D}' | '\u{208D}' | '\u{2329}' | '\u{3008}' | '\u{300A}' | '\u{300C}'
+            | '\u{300E}' | '\u{3010}' | '\u{3014}' | '\u{3016}' | '\u{3018}' | '\u{301A}'
+            | '\u{301D}' | '\u{FE59}' | '\u{FE5B}' | '\u{FE5D}' | '\u{FF08}' | '\u{FF3B}'
+            | '\u{FF5B}' | '\u{FF5F}' | '\u{FF62}' => OP,
+            ')' | ']' | '\u{FF09}' | '\u{FF3D}' => CP,
+            '}' | '\u{2046}' | '\u{207E}' | '\u{208E}' | '\u{232A}' | '\u{3001}' | '\u{3002}'
+            | '\u{3009}' | '\u{300B}' | '\u{300D}' | '\u{300F}' | '\u{3011}' | '\u{3015}'
+            | '\u{3017}' | '\u{3019}' | '\u{301B}' | '\u{301E}' | '\u{301F}' | '\u{FE50}'
+            | '\u{FE52}' | '\u{FE5A}' | '\u{FE5C}' | '\u{FE5E}' | '\u{FF0C}' | '\u{FF0E}'
+            | '\u{FF5D}' | '\u{FF60}' | '\u{FF61}' | '\u{FF63}' | '\u{FF64}' => CL,
+            '"' | '\'' | '\u{AB}' | '\u{BB}' | '\u{2018}' | '\u{2019}' | '\u{201B}'
+            | '\u{201C}' | '\u{201D}' | '\u{201F}' | '\u{2039}' | '\u{203A}' => QU,
+            '!' | '?' | '\u{FE15}' | '\u{FE16}' | '\u{FE56}' | '\u{FE57}' | '\u{FF01}'
+            | '\u{FF1F}' => EX,
+            ',' | '.' | ':' | ';' | '\u{37E}' | '\u{589}' | '\u{60C}' | '\u{60D}' | '\u{7F8}'
+            | '\u{2044}' | '\u{FE10}' | '\u{FE13}' | '\u{FE14}' => IS,
+            '/' => SY,
+            '0'..='9'
+            | '\u{0660}'..='\u{0669}'
+            | '\u{06F0}'..='\u{06F9}'
+            | '\u{0966}'..='\u{096F}' => NU,
+            '$'
+            | '+'
+            | '\\'
+            | '\u{A3}'
+            | '\u{A4}'
+            | '\u{A5}'
+            | '\u{B1}'
+            | '\u{20A0}'..='\u{20A6}'
+            | '\u{20A8}'..='\u{20B5}'
+            | '\u{20B7}'..='\u{20BA}'
+            | '\u{20BC}'..='\u{20CF}'
+            | '\u{2116}'
+            | '\u{2212}'
+            | '\u{2213}'
+            | '\u{FE69}'
+            | '\u{FF04}'
+            | '\u{FFE1}'
+            | '\u{FFE5}'
+            | '\u{FFE6}' => PR,
+            '%'
+            | '\u{A2}'
+            | '\u{B0}'
+            | '\u{2030}'..='\u{2037}'
+            | '\u{20A7}'
+            | '\u{20B6}'
+            | '\u{20BB}'
+            | '\u{2103}'
+            | '\u{2109}'
+            | '\u{FE6A}'
+            | '\u{FF05}'
+            | '\u{FFE0}' => PO,
+            '\u{2024}'..='\u{2026}' | '\u{22EF}' | '\u{FE19}' => IN,
+            // small kana and other non-starters (CJ is resolved to NS as allowed by LB1)
+            '\u{203C}'
+            | '\u{203D}'
+            | '\u{2047}'..='\u{2049}'
+            | '\u{3005}'
+            | '\u{301C}'
+            | '\u{303B}'
+            | '\u{303C}'
+            | '\u{3041}'
+            | '\u{3043}'
+            | '\u{3045}'
+            | '\u{3047}'
+            | '\u{3049}'
+            | '\u{3063}'
+            | '\u{3083}'
+            | '\u{3085}'
+            | '\u{3087}'
+            | '\u{308E}'
+            | '\u{3095}'
+            | '\u{3096}'
+            | '\u{309B}'..='\u{309E}'
+            | '\u{30A0}'
+            | '\u{30A1}'
+            | '\u{30A3}'
+            | '\u{30A5}'
+            | '\u{30A7}'
+            | '\u{30A9}'
+            | '\u{30C3}'
+            | '\u{30E3}'
+            | '\u{30E5}'
+            | '\u{30E7}'
+            | '\u{30EE}'
+            | '\u{30F5}'
+            | '\u{30F6}'
+            | '\u{30FB}'..='\u{30FE}'
+            | '\u{31F0}'..='\u{31FF}'
+            | '\u{FE54}'
+            | '\u{FE55}'
+            | '\u{FF1A}'
+            | '\u{FF1B}'
+            | '\u{FF65}'..='\u{FF70}'
+            | '\u{FF9E}'
+            | '\u{FF9F}' => NS,
+            '\u{1F1E6}'..='\u{1F1FF}' => RI,
+            '\u{1100}'..='\u{115F}'
+            | '\u{231A}'
+            | '\u{231B}'
+            | '\u{2E80}'..='\u{2FFF}'
+            | '\u{3003}'..='\u{3004}'
+            | '\u{3006}'..='\u{3007}'
+            | '\u{3012}'..='\u{3013}'
+            | '\u{3020}'..='\u{303A}'
+            | '\u{303D}'..='\u{30FF}'
+            | '\u{3100}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{A4CF}'
+            | '\u{A960}'..='\u{A97F}'
+            | '\u{AC00}'..='\u{D7FF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FE30}'..='\u{FE4F}'
+            | '\u{FF00}'..='\u{FF5A}'
+            | '\u{1F000}'..='\u{1FAFF}'
+            | '\u{20000}'..='\u{3FFFD}' => ID,
+            _ => AL,
+        }
+    }
+}
+
+/// The kind of line break opportunity yielded by [`LineBreaks`]. Positions that are not yielded
+/// at all are positions where a break is prohibited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BreakOpportunity {
+    /// The line **must** be broken here (after a line feed, form feed etc. and at the end of the input).
+    Mandatory,
+    /// The line **may** be broken here.
+    Allowed,
+}
+
+/// An iterator over the line break opportunities in a string as described by UAX #14. Each item is
+/// the byte offset that a new line would start at, along with the kind of break. Ansi escape
+/// sequences are skipped over, and any escape sequences directly preceding a character are kept
+/// with that character (so the offset points at the start of the escape sequence).
+///
+/// # Examples
+/// ```
+/// # use reflors::iter::{BreakOpportunity, LineBreaks};
+/// let breaks = LineBreaks::new("foo bar-baz\nqux").collect::<Vec<_>>();
+/// assert_eq!(
+///     breaks,
+///     vec![
+///         (4, BreakOpportunity::Allowed),
+///         (8, BreakOpportunity::Allowed),
+///         (12, BreakOpportunity::Mandatory),
+///         (15, BreakOpportunity::Mandatory),
+///     ]
+/// );
+/// ```
+#[allow(clippy::struct_excessive_bools)]
+pub struct LineBreaks<'a> {
+    input: &'a str,
+    tokens: Tokens<'a>,
+    /// The characters of the current run of text, and its byte offset in the input.
+    text: std::str::CharIndices<'a>,
+    text_start: usize,
+    escape_start: Option<usize>,
+    /// The class of the previous character (after LB9 / LB10 resolution).
+    prev: Option<LineBreakClass>,
+    /// The class of the last character before any run of spaces.
+    before_spaces: Option<LineBreakClass>,
+    prev_zwj: bool,
+    after_zw: bool,
+    ri_count: usize,
+    finished: bool,
+}
+
+impl<'a> LineBreaks<'a> {
+    /// Creates a new [`LineBreaks`] iterator over the given `input`.
+    #[must_use]
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            tokens: Tokens::new(input),
+            text: "".char_indices(),
+            text_start: 0,
+            escape_start: None,
+            prev: None,
+            before_spaces: None,
+            prev_zwj: false,
+            after_zw: false,
+            ri_count: 0,
+            finished: false,
+        }
+    }
+
+    /// Gets the next visible character and its byte offset, skipping escape sequences (and
+    /// remembering where the first one skipped started).
+    fn next_char(&mut self) -> Option<(usize, char)> {
+        loop {
+            if let Some((i, ch)) = self.text.next() {
+                return Some((self.text_start + i, ch));
+            }
+
+            let start = self.input.len() - self.tokens.remainder().len();
+            match self.tokens.next()? {
+                Token::Text(text) => {
+                    self.text = text.char_indices();
+                    self.text_start = start;
+                }
+                Token::Escape { .. } => {
+                    self.escape_start.get_or_insert(start);
+                }
+            }
+        }
+    }
+
+    /// Determines whether there is a break between the previous character and one of class `cur`,
+    /// returning `None` if a break is prohibited.
+    #[allow(clippy::match_same_arms, clippy::unnested_or_patterns)]
+    fn decide(&self, prev: LineBreakClass, cur: LineBreakClass) -> Option<BreakOpportunity> {
+        use LineBreakClass::{
+            AL, B2, BA, BB, BK, CL, CM, CP, CR, EX, GL, HY, ID, IN, IS, LF, NL, NS, NU, OP, PO, PR,
+            QU, RI, SP, SY, WJ, ZW, ZWJ,
+        };
+
+        let before = self.before_spaces.unwrap_or(prev);
+        // LB10: combining marks that can't be absorbed by the previous character act as AL
+        let cur = match cur {
+            CM | ZWJ if matches!(prev, SP | ZW) => AL,
+            _ => cur,
+        };
+
+        match (prev, cur) {
+            // LB4, LB5
+            (CR, LF) => None,
+            (BK | CR | LF | NL, _) => Some(BreakOpportunity::Mandatory),
+            // LB6, LB7
+            (_, BK | CR | LF | NL | SP | ZW) => None,
+            // LB8, LB8a
+            _ if self.after_zw => Some(BreakOpportunity::Allowed),
+            _ if self.prev_zwj => None,
+            // LB9 (`cur` is absorbed into `prev`, see `next`)
+            (_, CM | ZWJ) => None,
+            // LB11, LB12, LB12a
+            (WJ, _) | (_, WJ) | (GL, _) => None,
+            (_, GL) if !matches!(prev, SP | BA | HY) => None,
+            // LB13
+            (_, CL | CP | EX | IS | SY) => None,
+            // LB14 - LB17
+            _ if before == OP => None,
+            _ if before == QU && cur == OP => None,
+            _ if matches!(before, CL | CP) && cur == NS => None,
+            _ if before == B2 && cur == B2 => None,
+            // LB18
+            (SP, _) => Some(BreakOpportunity::Allowed),
+            // LB19, LB21, LB22
+            (QU, _) | (_, QU) | (BB, _) | (_, BA | HY | NS | IN) => None,
+            // LB23, LB23a, LB24
+            (AL, NU) | (NU, AL) | (PR, ID) | (ID, PO) | (PR | PO, AL) | (AL, PR | PO) => None,
+            // LB25
+            (CL | CP | NU, PO | PR) | (PO | PR, OP) | (PO | PR | HY | IS | NU | SY, NU) => None,
+            // LB28, LB29, LB30
+            (AL, AL) | (IS, AL) | (AL | NU, OP) | (CP, AL | NU) => None,
+            // LB30a
+            (RI, RI) if self.ri_count % 2 == 1 => None,
+            // LB31
+            _ => Some(BreakOpportunity::Allowed),
+        }
+    }
+}
+
+impl Iterator for LineBreaks<'_> {
+    type Item = (usize, BreakOpportunity);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use LineBreakClass::{AL, BK, CM, CR, LF, NL, RI, SP, ZW, ZWJ};
+
+        if self.finished {
+            return None;
+        }
+
+        while let Some((i, ch)) = self.next_char() {
+            let offset = self.escape_start.take().unwrap_or(i);
+            let cur = LineBreakClass::of(ch);
+            let Some(prev) = self.prev else {
+                // LB2, LB10
+                self.prev = Some(if matches!(cur, CM | ZWJ) { AL } else { cur });
+                self.prev_zwj = cur == ZWJ;
+                self.after_zw = cur == ZW;
+                self.ri_count = usize::from(cur == RI);
+                continue;
+            };
+
+            let decision = self.decide(prev, cur);
+
+            // LB9 / LB10: combining marks take on the class of the character they follow
+            let absorbed = matches!(cur, CM | ZWJ) && !matches!(prev, BK | CR | LF | NL | SP | ZW);
+            let resolved = match cur {
+                CM | ZWJ if absorbed => prev,
+                CM | ZWJ => AL,
+                _ => cur,
+            };
+
+            if resolved == SP {
+                if prev != SP {
+                    self.before_spaces = Some(prev);
+                }
+            } else {
+                self.before_spaces = None;
+            }
+            self.after_zw = cur == ZW || (self.after_zw && cur == SP);
+            self.prev_zwj = cur == ZWJ;
+            if !absorbed {
+                self.ri_count = if cur == RI { self.ri_count + 1 } else { 0 };
+            }
+            self.prev = Some(resolved);
+
+            if let Some(decision) = decision {
+                return Some((offset, decision));
+            }
+        }
+
+        // LB3
+        self.finished = true;
+        if self.input.is_empty() {
+            None
+        } else {
+            Some((self.input.len(), BreakOpportunity::Mandatory))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn segments(input: &str) -> Vec<&str> {
+        let mut start = 0;
+        LineBreaks::new(input)
+            .map(|(end, _)| {
+                let segment = &input[start..end];
+                start = end;
+                segment
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spaces_and_punctuation() {
+        assert_eq!(segments("Hello, world!"), vec!["Hello, ", "world!"]);
+        assert_eq!(segments("(foo) [bar]"), vec!["(foo) ", "[bar]"]);
+        assert_eq!(segments("\"quoted text\""), vec!["\"quoted ", "text\""]);
+        assert_eq!(
+            segments("cost: $12.50 (15%)"),
+            vec!["cost: ", "$12.50 ", "(15%)"]
+        );
+        assert_eq!(segments("wait... what"), vec!["wait... ", "what"]);
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(
+            segments("https://example.com/foo-bar/baz"),
+            vec!["https://", "example.com/", "foo-", "bar/", "baz"]
+        );
+    }
+
+    #[test]
+    fn joiners_and_spaces() {
+        assert_eq!(segments("foo\u{a0}bar baz"), vec!["foo\u{a0}bar ", "baz"]);
+        assert_eq!(segments("foo\u{2060}bar"), vec!["foo\u{2060}bar"]);
+        assert_eq!(segments("foo\u{200b}bar"), vec!["foo\u{200b}", "bar"]);
+        assert_eq!(segments("e\u{301}e\u{301}"), vec!["e\u{301}e\u{301}"]);
+    }
+
+    #[test]
+    fn cjk() {
+        assert_eq!(segments("東京です。"), vec!["東", "京", "で", "す。"]);
+        assert_eq!(segments("「東京」"), vec!["「東", "京」"]);
+        assert_eq!(segments("ちょっと"), vec!["ちょっ", "と"]);
+    }
+
+    #[test]
+    fn mandatory() {
+        let breaks = LineBreaks::new("foo\r\nbar\n").collect::<Vec<_>>();
+        assert_eq!(
+            breaks,
+            vec![
+                (5, BreakOpportunity::Mandatory),
+                (9, BreakOpportunity::Mandatory)
+            ]
+        );
+        assert_eq!(LineBreaks::new("").next(), None);
+    }
+
+    #[test]
+    fn ansi() {
+        let input = "\u{1b}[31mfoo\u{1b}[0m \u{1b}[1mbar\u{1b}[0m";
+        assert_eq!(
+            segments(input),
+            vec!["\u{1b}[31mfoo\u{1b}[0m ", "\u{1b}[1mbar\u{1b}[0m"]
+        );
+    }
+
+    #[test]
+    fn osc() {
+        // the url of a hyperlink is not broken up
+        let input = "\x1b]8;;https://example.com/a-b\x1b\\a link\x1b]8;;\x1b\\";
+        assert_eq!(
+            segments(input),
+            vec![
+                "\x1b]8;;https://example.com/a-b\x1b\\a ",
+                "link\x1b]8;;\x1b\\"
+            ]
+        );
+    }
+}
@@ -4,4 +4,12 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+mod chars;
+mod linebreak;
+mod tokens;
 mod vis;
+pub use chars::{VisibleChar, VisibleChars};
+pub use linebreak::{BreakOpportunity, LineBreakClass, LineBreaks};
+pub(crate) use tokens::EscapeBuffer;
+pub use tokens::{EscapeKind, Token, Tokens};
+pub use vis::Viserator;
@@ -0,0 +1,352 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::borrow::Cow;
+
+use crate::{ansi, Result};
+
+const BEL: u8 = 0x07;
+const ESC: u8 = 0x1b;
+
+/// The kind of an ansi escape sequence, determined by the character following the escape.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EscapeKind {
+    /// A control sequence (`ESC [`), such as the SGR sequences that set colors and styles.
+    Csi,
+    /// An operating system command (`ESC ]`), such as a hyperlink or a window title, terminated
+    /// by `BEL` or `ESC \`.
+    Osc,
+    /// Any other escape sequence.
+    Other,
+}
+
+/// A piece of text yielded by [`Tokens`], borrowed from the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Token<'a> {
+    /// A run of text without any escape sequences.
+    Text(&'a str),
+    /// A single escape sequence.
+    Escape {
+        /// The kind of the escape sequence.
+        kind: EscapeKind,
+        /// The whole escape sequence, including the escape and its terminator.
+        raw: &'a str,
+        /// The parameters of the escape sequence, i.e. everything between its introducer (`ESC [`,
+        /// `ESC ]` or `ESC`) and its terminator, like `1;31` in `\x1b[1;31m`.
+        params: &'a str,
+    },
+}
+
+impl<'a> Token<'a> {
+    /// Gets the text of this [`Token`] as it is in the input.
+    #[must_use]
+    pub const fn as_str(&self) -> &'a str {
+        match *self {
+            Self::Text(text) => text,
+            Self::Escape { raw, .. } => raw,
+        }
+    }
+
+    /// Whether this [`Token`] is an SGR escape sequence, which sets the style of the text after it.
+    #[must_use]
+    pub fn is_sgr(&self) -> bool {
+        matches!(self, Self::Escape { kind: EscapeKind::Csi, raw, .. } if raw.ends_with('m'))
+    }
+
+    /// Whether this [`Token`] is an SGR escape sequence resetting the style (`\x1b[0m` or
+    /// `\x1b[m`).
+    #[must_use]
+    pub fn is_reset(&self) -> bool {
+        matches!(
+            self,
+            Self::Escape { kind: EscapeKind::Csi, raw, params: "" | "0" } if raw.ends_with('m')
+        )
+    }
+
+    /// Whether this [`Token`] is complete, i.e. it's text or an escape sequence ending with its
+    /// terminator. Only the last token of the input can be incomplete.
+    #[must_use]
+    pub fn is_terminated(&self) -> bool {
+        match *self {
+            Self::Text(_) => true,
+            Self::Escape {
+                kind: EscapeKind::Osc,
+                raw,
+                ..
+            } => raw.ends_with('\x07') || raw.ends_with("\x1b\\"),
+            Self::Escape { raw, .. } => {
+                raw.len() > 1 && raw.chars().next_back().is_some_and(ansi::is_terminator)
+            }
+        }
+    }
+}
+
+/// An iterator splitting a string into runs of text and the ansi escape sequences between them,
+/// without allocating.
+///
+/// Control sequences and other escape sequences end at the first terminating character (see
+/// [`ansi::is_terminator`]), the same way every other part of this crate finds their end.
+/// Operating system commands end at `BEL` or `ESC \` instead, since they may contain any character.
+/// An escape sequence that isn't terminated runs until the end of the input.
+///
+/// # Examples
+/// ```
+/// # use reflors::iter::{EscapeKind, Token, Tokens};
+/// let tokens = Tokens::new("\x1b[1;31mred\x1b[0m!").collect::<Vec<_>>();
+/// assert_eq!(
+///     tokens,
+///     vec![
+///         Token::Escape { kind: EscapeKind::Csi, raw: "\x1b[1;31m", params: "1;31" },
+///         Token::Text("red"),
+///         Token::Escape { kind: EscapeKind::Csi, raw: "\x1b[0m", params: "0" },
+///         Token::Text("!"),
+///     ]
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct Tokens<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    /// Creates a new [`Tokens`] iterator over the given `input`.
+    #[must_use]
+    pub const fn new(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    /// Creates a new [`Tokens`] iterator over the given `bytes`.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If `bytes` is not valid utf8
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        Ok(Self::new(std::str::from_utf8(bytes)?))
+    }
+
+    /// Gets the part of the input that hasn't been yielded yet.
+    #[must_use]
+    pub fn remainder(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    /// Finds the end of the escape sequence at the start of `bytes`, returning the kind of the
+    /// sequence, the range of its parameters and its length.
+    fn escape(bytes: &[u8]) -> (EscapeKind, (usize, usize), usize) {
+        if bytes.get(1) == Some(&b']') {
+            let mut i = 2;
+            while i < bytes.len() {
+                match bytes[i] {
+                    BEL => return (EscapeKind::Osc, (2, i), i + 1),
+                    ESC if bytes.get(i + 1) == Some(&b'\\') => {
+                        return (EscapeKind::Osc, (2, i), i + 2)
+                    }
+                    _ => i += 1,
+                }
+            }
+            return (EscapeKind::Osc, (2, bytes.len()), bytes.len());
+        }
+
+        let (kind, start) = if bytes.get(1) == Some(&b'[') {
+            (EscapeKind::Csi, 2)
+        } else {
+            (EscapeKind::Other, 1)
+        };
+        match bytes[1..]
+            .iter()
+            .position(|&b| ansi::is_terminator(b as char))
+        {
+            Some(i) => (kind, (start, i + 1), i + 2),
+            None => (kind, (start.min(bytes.len()), bytes.len()), bytes.len()),
+        }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.remainder();
+        let bytes = rest.as_bytes();
+        if bytes.is_empty() {
+            return None;
+        }
+
+        if bytes[0] == ESC {
+            let (kind, (start, end), len) = Self::escape(bytes);
+            self.pos += len;
+            return Some(Token::Escape {
+                kind,
+                raw: &rest[..len],
+                params: &rest[start..end],
+            });
+        }
+
+        let len = bytes.iter().position(|&b| b == ESC).unwrap_or(bytes.len());
+        self.pos += len;
+        Some(Token::Text(&rest[..len]))
+    }
+}
+
+/// Holds back an escape sequence that is split across writes until its terminator is written, so
+/// streaming writers can split every write into [`Tokens`] the same way a whole string would be.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EscapeBuffer {
+    held: String,
+}
+
+impl EscapeBuffer {
+    /// Gets the part of the text written so far (including `s`) that ends before an unterminated
+    /// escape sequence, holding that sequence back for the next write.
+    pub(crate) fn complete<'a>(&mut self, s: &'a str) -> Cow<'a, str> {
+        let mut input = if self.held.is_empty() {
+            Cow::Borrowed(s)
+        } else {
+            let mut held = std::mem::take(&mut self.held);
+            held.push_str(s);
+            Cow::Owned(held)
+        };
+
+        if let Some(last) = Tokens::new(&input).last().filter(|t| !t.is_terminated()) {
+            let start = input.len() - last.as_str().len();
+            self.held = input[start..].to_string();
+            match input {
+                Cow::Borrowed(s) => input = Cow::Borrowed(&s[..start]),
+                Cow::Owned(ref mut s) => s.truncate(start),
+            }
+        }
+
+        input
+    }
+
+    /// Takes the unterminated escape sequence held back, if any, e.g. to write it out when the
+    /// writer is closed.
+    pub(crate) fn take(&mut self) -> String {
+        std::mem::take(&mut self.held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn escape<'a>(kind: EscapeKind, raw: &'a str, params: &'a str) -> Token<'a> {
+        Token::Escape { kind, raw, params }
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(Tokens::new("").next(), None);
+        assert_eq!(
+            Tokens::new("東京 text\n").collect::<Vec<_>>(),
+            vec![Token::Text("東京 text\n")]
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let input = "a\x1b[38;5;1m\x1b[mb\x1b(B\x1b[2Kc";
+        assert_eq!(
+            Tokens::new(input).collect::<Vec<_>>(),
+            vec![
+                Token::Text("a"),
+                escape(EscapeKind::Csi, "\x1b[38;5;1m", "38;5;1"),
+                escape(EscapeKind::Csi, "\x1b[m", ""),
+                Token::Text("b"),
+                escape(EscapeKind::Other, "\x1b(B", "("),
+                escape(EscapeKind::Csi, "\x1b[2K", "2"),
+                Token::Text("c"),
+            ]
+        );
+        assert_eq!(
+            Tokens::new(input).map(|t| t.as_str()).collect::<String>(),
+            input
+        );
+
+        let sgr = Tokens::new(input)
+            .map(|t| (t.is_sgr(), t.is_reset()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sgr,
+            vec![
+                (false, false),
+                (true, false),
+                (true, true),
+                (false, false),
+                (false, false),
+                (false, false),
+                (false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07";
+        assert_eq!(
+            Tokens::new(link).collect::<Vec<_>>(),
+            vec![
+                escape(
+                    EscapeKind::Osc,
+                    "\x1b]8;;https://example.com\x1b\\",
+                    "8;;https://example.com"
+                ),
+                Token::Text("link"),
+                escape(EscapeKind::Osc, "\x1b]8;;\x07", "8;;"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated() {
+        assert_eq!(
+            Tokens::new("a\x1b[1;3").collect::<Vec<_>>(),
+            vec![Token::Text("a"), escape(EscapeKind::Csi, "\x1b[1;3", "1;3")]
+        );
+        assert_eq!(
+            Tokens::new("\x1b").collect::<Vec<_>>(),
+            vec![escape(EscapeKind::Other, "\x1b", "")]
+        );
+        assert_eq!(
+            Tokens::new("\x1b]0;title").collect::<Vec<_>>(),
+            vec![escape(EscapeKind::Osc, "\x1b]0;title", "0;title")]
+        );
+    }
+
+    #[test]
+    fn terminated() {
+        for (input, expected) in [
+            ("text", true),
+            ("\x1b[1m", true),
+            ("\x1bm", true),
+            ("\x1b]8;;\x07", true),
+            ("\x1b]8;;\x1b\\", true),
+            ("\x1b", false),
+            ("\x1b[", false),
+            ("\x1b[1;3", false),
+            ("\x1b]8;;url\x1b", false),
+        ] {
+            let token = Tokens::new(input).next().unwrap();
+            assert_eq!(token.is_terminated(), expected, "{input:?}");
+        }
+
+        let mut buffer = EscapeBuffer::default();
+        assert_eq!(buffer.complete("a\x1b]8;;url"), "a");
+        assert_eq!(buffer.complete("\x1b"), "");
+        assert_eq!(buffer.complete("\\link\x1b["), "\x1b]8;;url\x1b\\link");
+        assert_eq!(buffer.take(), "\x1b[");
+        assert_eq!(buffer.complete("b"), "b");
+    }
+
+    #[test]
+    fn bytes() {
+        let tokens = Tokens::from_bytes(b"\x1b[1mbold").unwrap();
+        assert_eq!(tokens.remainder(), "\x1b[1mbold");
+        assert_eq!(tokens.count(), 2);
+        assert!(Tokens::from_bytes(&[0x1b, 0xff]).is_err());
+    }
+}
@@ -6,44 +6,105 @@
 
 use crate::ansi;
 
-/// An iterator over the **visible** characters in a string.
+use super::{Token, Tokens};
+
+/// An iterator over the **visible** characters in a string, skipping ansi escape sequences and
+/// zero-width characters (see [`ansi::is_zero_width`]).
 ///
 /// Hence it is a Vis(ible-It)erator! I'm such a word ...guy.
+///
+/// It can be walked from either end (or both), escape sequences are always found by splitting the
+/// text into [`Tokens`] from the front, so both directions skip exactly the same sequences. Walking
+/// from the back records where the escape sequences are the first time, so reverse traversal stays
+/// linear.
+///
+/// # Examples
+/// ```
+/// # use reflors::iter::Viserator;
+/// let visible = Viserator::new("\x1b[1msoft\u{ad}ware\x1b[0m").collect::<String>();
+/// assert_eq!(visible, "software");
+/// let reversed = Viserator::new("\x1b[1msoft\u{ad}ware\x1b[0m").rev().collect::<String>();
+/// assert_eq!(reversed, "erawtfos");
+/// ```
 pub struct Viserator<'a> {
     input: &'a str,
-    chars: std::str::Chars<'a>,
-    pos: usize,
-    in_ansi: bool,
+    /// The byte offset of the next character from the front.
+    front: usize,
+    /// The byte offset just past the next character from the back.
+    back: usize,
+    /// The byte ranges of the escape sequences before `back`, recorded on the first call to
+    /// [`next_back`](`DoubleEndedIterator::next_back`).
+    escapes: Option<Vec<(usize, usize)>>,
 }
 
 impl<'a> Viserator<'a> {
+    /// Creates a new [`Viserator`] over the given `input`.
+    #[must_use]
     pub fn new(input: &'a str) -> Self {
         Self {
             input,
-            chars: input.chars(),
-            pos: 0,
-            in_ansi: false,
+            front: 0,
+            back: input.len(),
+            escapes: None,
         }
     }
 
-    // pub fn new_at(input: &'a str, pos: usize) -> Self {
-    //     Self { input, pos }
-    // }
+    /// Gets the byte ranges of the escape sequences between `front` and `back`.
+    fn find_escapes(&self) -> Vec<(usize, usize)> {
+        let mut offset = self.front;
+        let mut escapes = Vec::new();
+        for token in Tokens::new(&self.input[self.front..self.back]) {
+            let len = token.as_str().len();
+            if let Token::Escape { .. } = token {
+                escapes.push((offset, offset + len));
+            }
+            offset += len;
+        }
+        escapes
+    }
 }
 
-impl<'a> Iterator for Viserator<'a> {
+impl Iterator for Viserator<'_> {
     type Item = char;
 
-    #[allow(clippy::while_let_on_iterator)]
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(ch) = self.chars.next() {
+        while let Some(ch) = self.input[self.front..self.back].chars().next() {
             if ch == ansi::MARKER {
-                self.in_ansi = true;
-            } else if self.in_ansi {
-                if ansi::is_terminator(ch) {
-                    self.in_ansi = false;
-                }
+                // only the escape sequence is split off, text is walked one char at a time
+                let escape = Tokens::new(&self.input[self.front..self.back]).next()?;
+                self.front += escape.as_str().len();
             } else {
+                self.front += ch.len_utf8();
+                if !ansi::is_zero_width(ch) {
+                    return Some(ch);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl DoubleEndedIterator for Viserator<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.escapes.is_none() {
+            self.escapes = Some(self.find_escapes());
+        }
+
+        while self.back > self.front {
+            let escapes = self.escapes.as_mut()?;
+            if let Some(&(start, end)) = escapes.last() {
+                if end >= self.back {
+                    // the escape sequence runs until `back`
+                    self.back = start;
+                    escapes.pop();
+                    continue;
+                }
+            }
+
+            let ch = self.input[self.front..self.back].chars().next_back()?;
+            self.back -= ch.len_utf8();
+            if !ansi::is_zero_width(ch) {
                 return Some(ch);
             }
         }
@@ -51,3 +112,68 @@ impl<'a> Iterator for Viserator<'a> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn both_ways(input: &str) -> (String, String) {
+        let forward = Viserator::new(input).collect::<String>();
+        let mut backward = Viserator::new(input).rev().collect::<Vec<_>>();
+        backward.reverse();
+        (forward, backward.into_iter().collect())
+    }
+
+    #[test]
+    fn reverse() {
+        for (input, expected) in [
+            ("", ""),
+            ("plain 東京", "plain 東京"),
+            ("\x1b[1mbold\x1b[0m \x1b[38;5;1mred\x1b[0m", "bold red"),
+            ("a\u{200b}b\u{fe0f}", "ab"),
+            ("\x1b[1m\x1b[0m", ""),
+            ("text\x1b[1;3", "text"),
+            ("\x1b\x1b[1mx", "x"),
+            ("a\x1bmb", "ab"),
+        ] {
+            assert_eq!(both_ways(input), (expected.into(), expected.into()));
+        }
+    }
+
+    #[test]
+    fn both_ends() {
+        let mut vis = Viserator::new("\x1b[1mab\x1b[0mc\x1b[31md\x1b[0m");
+        assert_eq!(vis.next_back(), Some('d'));
+        assert_eq!(vis.next(), Some('a'));
+        assert_eq!(vis.next_back(), Some('c'));
+        assert_eq!(vis.next_back(), Some('b'));
+        assert_eq!(vis.next(), None);
+        assert_eq!(vis.next_back(), None);
+    }
+
+    #[test]
+    fn escapes_recorded_once() {
+        // the front may pass escape sequences that were recorded from the back
+        let mut vis = Viserator::new("\x1b[1ma\x1b[2mb\x1b[3mc\x1b[0m");
+        assert_eq!(vis.next_back(), Some('c'));
+        assert_eq!(vis.next(), Some('a'));
+        assert_eq!(vis.next(), Some('b'));
+        assert_eq!(vis.next_back(), None);
+        assert_eq!(vis.next(), None);
+
+        // the last few visible characters of a long styled text
+        let text = "\x1b[31mx\x1b[0m".repeat(1000) + "\x1b[1mend";
+        assert_eq!(
+            Viserator::new(&text).rev().take(4).collect::<String>(),
+            "dnex"
+        );
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(both_ways(link), ("link".into(), "link".into()));
+        assert_eq!(Viserator::new(link).rev().collect::<String>(), "knil");
+    }
+}
@@ -49,37 +49,64 @@ pub mod bytes {
 mod error;
 pub use error::{Error, Result};
 
+mod ext;
+pub use ext::ReflowExt;
+
 /// # `ansi` Module
 ///
 /// Contains constants for ansi escape code sequence start bytes, as well as functions to whether a character is a terminating char.
+/// It also maps between visible columns and byte offsets (see [`ColumnIndex`](`crate::ansi::ColumnIndex`)),
+/// and [`AnsiString`](`crate::ansi::AnsiString`) is an owned string that knows its visible width.
 ///
-/// #### Status: Mostly complete, need to make a decision about [`Writer`](`crate::ansi::writer::Writer`) and the best way to handle it.
+/// #### Status: Mostly complete. [`Writer`](`crate::ansi::writer::Writer`) now writes to any [`std::io::Write`].
 ///
 /// Source: [muesli/reflow/ansi](https://github.com/muesli/reflow/ansi/)
 pub mod ansi;
 
 /// # `dedent` Module
 ///
-/// Contains code to un-indent text, taking ansi escape codes into account.
+/// Contains code to un-indent text, either all at once or streamed through a [`Writer`](`crate::dedent::Writer`),
+/// taking ansi escape codes into account.
 ///
 /// #### Status: Complete, maybe add more tests to make sure it works on all inputs and weed out any edge cases.
 ///
 /// Source: [muesli/reflow/dedent](https://github.com/muesli/reflow/dedent/)
 pub mod dedent;
 
+/// # `fmt` Module
+///
+/// Contains glue between the writers in this crate and [`std::fmt`], such as the
+/// [`FmtWriter`](`crate::fmt::FmtWriter`) adapter for writing into any [`std::fmt::Write`] target,
+/// and the [`Visible`](`crate::fmt::Visible`) wrapper for padding and truncating styled text with
+/// the regular formatting syntax.
+///
+/// #### Status: Complete, this one doesn't exist in the go version.
+pub mod fmt;
+
+/// # `hyphenate` Module
+///
+/// Contains a Liang-style hyphenator (with embedded US English patterns) used to hyphenate long
+/// words when word-wrapping, taking ansi escape codes and soft hyphens into account.
+///
+/// #### Status: Complete, this one doesn't exist in the go version.
+pub mod hyphenate;
+
 /// # `indent` Module
 ///
-/// Contains code to indent text.
+/// Contains code to indent text, either all at once or streamed through a [`Writer`](`crate::indent::Writer`).
 ///
-/// #### Status: Mostly complete, except for the below item.
-/// TODO: The original golang version has indentation implemented as another `Writer`, but I'm not sure if that's necessary.
+/// #### Status: Complete.
 ///
 /// Source: [muesli/reflow/indent](https://github.com/muesli/reflow/indent/)
 pub mod indent;
 
 /// # `iter` Module
 ///
-/// Contains useful iterators for dealing with ansi-aware strings and bytes.
+/// Contains useful iterators for dealing with ansi-aware strings and bytes, such as the
+/// [`Viserator`](`crate::iter::Viserator`) over visible characters, its richer
+/// [`VisibleChars`](`crate::iter::VisibleChars`) counterpart yielding the position and style of
+/// each character, the [`Tokens`](`crate::iter::Tokens`) lexer splitting text into runs of text and
+/// escape sequences, and the [`LineBreaks`](`crate::iter::LineBreaks`) iterator.
 pub mod iter;
 
 /// # `margin` Module
@@ -91,28 +118,55 @@ pub mod margin;
 
 /// # `padding` Module
 ///
-/// TODO: Describe module here.
+/// Contains code to pad lines to a minimum width, either all at once or streamed through a
+/// [`Writer`](`crate::padding::Writer`), taking ansi escape codes into account.
+///
+/// #### Status: Complete.
 ///
 /// Source: [muesli/reflow/padding](https://github.com/muesli/reflow/padding/)
 pub mod padding;
 
+/// # `pipeline` Module
+///
+/// Contains the [`Reflow`](`crate::pipeline::Reflow`) trait implemented by the writers in this
+/// crate, and a [`Pipeline`](`crate::pipeline::Pipeline`) that chains them together, e.g. to wrap,
+/// indent and then pad text in one go.
+///
+/// #### Status: Complete, this one doesn't exist in the go version.
+pub mod pipeline;
+
 /// # `truncate` Module
 ///
-/// TODO: Describe module here.
+/// Contains code to truncate text to a maximum width with an optional tail, either all at once or
+/// streamed through a [`Writer`](`crate::truncate::Writer`), taking ansi escape codes into account.
+/// The text can be cut at its end, its start or its middle (see
+/// [`TruncateMode`](`crate::truncate::TruncateMode`)), and whole blocks of text can be limited to
+/// a number of lines with [`truncate_lines`](`crate::truncate::truncate_lines`).
+///
+/// #### Status: Complete.
 ///
 /// Source: [muesli/reflow/truncate](https://github.com/muesli/reflow/truncate/)
 pub mod truncate;
 
 /// # `wordwrap` Module
 ///
-/// TODO: Describe module here.
+/// Contains code to wrap text at word boundaries, taking ansi escape codes into account. Text can be
+/// wrapped greedily (like the go version) or optimally, minimizing the raggedness of each paragraph,
+/// breaking lines either at whitespace and a set of breakpoints or at the break opportunities found
+/// by the Unicode Line Breaking Algorithm. Markdown and source-code comments can also be reflowed
+/// while preserving their structure.
+///
+/// #### Status: Greedy, optimal, markdown and comment wrapping complete.
 ///
 /// Source: [muesli/reflow/wordwrap](https://github.com/muesli/reflow/wordwrap/)
 pub mod wordwrap;
 
 /// # `wrap` Module
 ///
-/// TODO: Describe module here.
+/// Contains code to hard-wrap text at a given width regardless of word boundaries, taking ansi
+/// escape codes into account.
+///
+/// #### Status: Complete.
 ///
 /// Source: [muesli/reflow/wrap](https://github.com/muesli/reflow/wrap/)
 pub mod wrap;
@@ -5,3 +5,136 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 mod writer;
+pub use writer::{pad_bytes, pad_string, Writer};
+
+use crate::ansi;
+
+/// How lines are aligned within a given width.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Alignment {
+    /// Pad lines on the right.
+    #[default]
+    Left,
+    /// Pad lines on the left.
+    Right,
+    /// Pad lines on both sides, with the extra space (if any) on the right.
+    Center,
+}
+
+/// Aligns every line of the given string within `width` visible columns by padding it with
+/// spaces. Lines that are already wider are left as they are.
+///
+/// # Examples
+/// ```
+/// # use reflors::padding::{align_str, Alignment};
+/// assert_eq!(align_str("one\nthree", 6, Alignment::Right), "   one\n three");
+/// assert_eq!(align_str("\x1b[1mone\x1b[0m", 6, Alignment::Center), " \x1b[1mone\x1b[0m  ");
+/// ```
+#[must_use]
+pub fn align_str(input: &str, width: usize, alignment: Alignment) -> String {
+    let mut output = String::with_capacity(input.len());
+    for line in input.split_inclusive('\n') {
+        let content = line.strip_suffix('\n').unwrap_or(line);
+        let content = content.strip_suffix('\r').unwrap_or(content);
+        let padding = width.saturating_sub(ansi::visible_width(content));
+        let left = match alignment {
+            Alignment::Left => 0,
+            Alignment::Right => padding,
+            Alignment::Center => padding / 2,
+        };
+
+        output.push_str(&" ".repeat(left));
+        output.push_str(content);
+        output.push_str(&" ".repeat(padding - left));
+        output.push_str(&line[content.len()..]);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fmt::FmtWriter;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn padding() {
+        assert_eq!(
+            pad_string("one\ntwo2\n\nthree", 4).unwrap(),
+            "one \ntwo2\n    \nthree"
+        );
+        assert_eq!(pad_string("one\r\ntwo", 4).unwrap(), "one \r\ntwo ");
+        assert_eq!(pad_string("one", 0).unwrap(), "one");
+        assert_eq!(pad_bytes(b"one", 4).unwrap(), b"one ");
+        assert_eq!(pad_string("\tx\nab\tx", 10).unwrap(), "\tx \nab\tx ");
+    }
+
+    #[test]
+    fn ansi() {
+        assert_eq!(
+            pad_string("\x1b[31mone\ntwo\x1b[0m", 4).unwrap(),
+            "\x1b[31mone \x1b[0m\ntwo\x1b[0m "
+        );
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(pad_string(link, 6).unwrap(), format!("{link}  "));
+
+        // also when it is written in pieces
+        let mut writer = Writer::new(6);
+        for chunk in link.as_bytes().chunks(3) {
+            writer.write_bytes(chunk).unwrap();
+        }
+        writer.close().unwrap();
+        assert_eq!(writer.to_string().unwrap(), format!("{link}  "));
+    }
+
+    #[test]
+    fn align() {
+        let text = "a\r\nbbb\n\nccccc";
+        assert_eq!(
+            align_str(text, 4, Alignment::Left),
+            "a   \r\nbbb \n    \nccccc"
+        );
+        assert_eq!(
+            align_str(text, 4, Alignment::Right),
+            "   a\r\n bbb\n    \nccccc"
+        );
+        assert_eq!(
+            align_str(text, 4, Alignment::Center),
+            " a  \r\nbbb \n    \nccccc"
+        );
+    }
+
+    #[test]
+    fn unterminated_escape() {
+        // an escape sequence that is never terminated is still written when closing
+        assert_eq!(pad_string("ab\x1b[1", 4).unwrap(), "ab  \x1b[1");
+        let mut writer = Writer::new(4);
+        writer.write_str("ab\x1b]8;;https://").unwrap();
+        writer.write_str("example.com").unwrap();
+        writer.close().unwrap();
+        assert_eq!(
+            writer.to_string().unwrap(),
+            "ab  \x1b]8;;https://example.com"
+        );
+    }
+
+    #[test]
+    fn piped() {
+        let text = "\x1b[1m東京\x1b[0m\r\nsecond line\n";
+        let expected = pad_string(text, 6).unwrap();
+        assert_eq!(expected, "\x1b[1m東京\x1b[0m  \r\nsecond line\n");
+        for size in 1..text.len() {
+            let mut writer = Writer::new_piped(6, FmtWriter::new(String::new()));
+            for chunk in text.as_bytes().chunks(size) {
+                assert_eq!(writer.write_bytes(chunk).unwrap(), chunk.len());
+            }
+            writer.close().unwrap();
+            assert_eq!(writer.into_inner().into_inner(), expected);
+        }
+    }
+}
@@ -4,78 +4,167 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::io::Write;
-
-use bytes::{buf::Writer as BufWriter, BufMut, BytesMut};
+use std::io::{Result as IoResult, Write as IoWrite};
 
 use crate::{
     ansi::{self, writer::Writer as AnsiWriter},
+    fmt::Utf8Decoder,
+    iter::{EscapeBuffer, Token, Tokens},
+    pipeline::{self, Reflow},
     Error, Result,
 };
 
-pub struct Writer<B> {
+/// A streaming, ansi-aware padding writer, which pads every line with spaces until it is at least
+/// `padding` columns wide. The active style is reset at the end of each line, so it doesn't bleed
+/// into the padding of the next one.
+///
+/// Source: [muesli/reflow/padding/padding.go](https://github.com/muesli/reflow/blob/master/padding/padding.go)
+pub struct Writer<W> {
     padding: usize,
-    ansi_writer: AnsiWriter<B>,
-    buf: BytesMut,
-    cache: BytesMut,
+    ansi_writer: AnsiWriter<W>,
     line_len: usize,
-    in_ansi: bool,
+    escapes: EscapeBuffer,
+    pending_cr: bool,
     char_buffer: [u8; 4],
+    decoder: Utf8Decoder,
 }
 
-impl Writer<BytesMut> {
+impl Writer<Vec<u8>> {
+    /// Creates a new [`Writer`] that pads lines to `padding` columns using an internal buffer.
+    #[must_use]
     pub fn new(padding: usize) -> Self {
-        Self {
-            padding,
-            ansi_writer: AnsiWriter::new(BytesMut::new().writer()),
-            buf: BytesMut::new(),
-            cache: BytesMut::new(),
-            line_len: 0,
-            in_ansi: false,
-            char_buffer: [0; 4],
-        }
+        Self::new_piped(padding, Vec::new())
     }
 
+    /// Creates a new [`Writer`] that pads lines to `padding` columns using an internal buffer with
+    /// the given capacity.
+    #[must_use]
     pub fn with_capacity(padding: usize, cap: usize) -> Self {
-        Self {
+        Self::new_piped(padding, Vec::with_capacity(cap))
+    }
+
+    /// Gets a copy of the padded output written so far.
+    #[must_use]
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.ansi_writer.get_ref().clone()
+    }
+
+    /// Tries to create a string from the padded output written so far.
+    ///
+    /// ## Errors
+    /// - `crate::Error::Utf8` if the buffer is not a valid utf8 string
+    pub fn to_string(&self) -> Result<String> {
+        String::from_utf8(self.to_vec()).map_err(Error::from)
+    }
+}
+
+impl<W> Writer<W> {
+    /// Creates a new [`Writer`] that pads lines to `padding` columns and writes them to `writer`.
+    #[must_use]
+    pub fn new_piped(padding: usize, writer: W) -> Self {
+        Writer {
             padding,
-            ansi_writer: AnsiWriter::new(BytesMut::with_capacity(cap).writer()),
-            buf: BytesMut::with_capacity(cap),
-            cache: BytesMut::with_capacity(cap),
+            ansi_writer: AnsiWriter::new(writer),
             line_len: 0,
-            in_ansi: false,
+            escapes: EscapeBuffer::default(),
+            pending_cr: false,
             char_buffer: [0; 4],
+            decoder: Utf8Decoder::default(),
         }
     }
 
+    /// Gets the width lines are padded to.
+    #[must_use]
+    pub fn padding(&self) -> usize {
+        self.padding
+    }
+
+    /// **Consumes** this [`Writer`] and returns the underlying writer, without
+    /// [closing](`Writer::close`) it.
+    pub fn into_inner(self) -> W {
+        self.ansi_writer.into_inner()
+    }
+}
+
+impl<W: IoWrite> Writer<W> {
+    /// Write the given bytes to this [`Writer`]. A utf8 character may be split across writes, its
+    /// start is held back until the rest of it is written.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If `bytes` can't be valid utf8
+    /// - `Error::Io` - If writing to the underlying writer fails
     pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
-        // TODO: Remove unwrap, make Error/Result
-        let s = std::str::from_utf8(bytes).unwrap();
-        self.write_str(s)
+        let s = self.decoder.decode(bytes)?;
+        self.write_str(&s)?;
+        Ok(bytes.len())
     }
 
+    /// Write the given string to this [`Writer`].
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
     pub fn write_str(&mut self, s: &str) -> Result<usize> {
-        for ch in s.chars() {
-            if ch == ansi::MARKER {
-                self.in_ansi = true;
-            } else if self.in_ansi {
-                if ansi::is_terminator(ch) {
-                    self.in_ansi = false;
+        let input = self.escapes.complete(s);
+        for token in Tokens::new(&input) {
+            let text = match token {
+                Token::Text(text) => text,
+                Token::Escape { raw, .. } => {
+                    if std::mem::take(&mut self.pending_cr) {
+                        self.ansi_writer.write_str("\r")?;
+                    }
+                    self.ansi_writer.write_str(raw)?;
+                    continue;
                 }
-            } else {
-                self.line_len += char::len_utf8(ch);
+            };
+
+            for ch in text.chars() {
+                // a carriage return is held back so the padding goes before a `\r\n` line ending
+                let cr = std::mem::take(&mut self.pending_cr);
+                if cr && ch != '\n' {
+                    self.ansi_writer.write_str("\r")?;
+                }
+
+                if ch == '\r' {
+                    self.pending_cr = true;
+                    continue;
+                }
+
+                self.line_len += ansi::char_width_at(ch, self.line_len);
                 if ch == '\n' {
                     self.pad()?;
                     self.ansi_writer.reset_ansi()?;
                     self.line_len = 0;
+                    if cr {
+                        self.ansi_writer.write_str("\r")?;
+                    }
                 }
+
+                let encoded = ch.encode_utf8(&mut self.char_buffer);
+                self.ansi_writer.write_str(encoded)?;
             }
+        }
+
+        Ok(s.len())
+    }
 
-            let encoded = ch.encode_utf8(&mut self.char_buffer);
-            let _ = self.ansi_writer.write_str(encoded)?;
+    /// Finishes padding by padding the last line (if it isn't empty), writing an escape sequence
+    /// left unterminated and flushing the underlying writer.
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn close(&mut self) -> Result<()> {
+        if self.line_len != 0 {
+            self.pad()?;
+        }
+        if std::mem::take(&mut self.pending_cr) {
+            self.ansi_writer.write_str("\r")?;
         }
+        // written last, since it would swallow anything written after it
+        let held = self.escapes.take();
+        self.ansi_writer.write_raw(held.as_bytes())?;
 
-        Ok(0)
+        self.line_len = 0;
+        self.ansi_writer.flush_writer()
     }
 
     fn pad(&mut self) -> Result<()> {
@@ -86,56 +175,54 @@ impl Writer<BytesMut> {
 
         Ok(())
     }
+}
 
-    pub fn close(&mut self) -> Result<()> {
-        self.flush()
+impl Reflow for Writer<Vec<u8>> {
+    fn write(&mut self, input: &str, out: &mut String) -> Result<()> {
+        self.write_str(input)?;
+        pipeline::drain(self.ansi_writer.get_mut(), out)
     }
 
-    fn flush(&mut self) -> Result<()> {
-        use bytes::{Buf, BufMut};
-        use std::io::{Read, Write};
-        if self.line_len != 0 {
-            self.pad()?;
-        }
-
-        self.cache.clear();
-        self.cache.resize(self.buf.len(), 0);
-        self.cache.copy_from_slice(&self.buf);
-        self.line_len = 0;
-        self.in_ansi = false;
+    fn finish(&mut self, out: &mut String) -> Result<()> {
+        self.close()?;
+        pipeline::drain(self.ansi_writer.get_mut(), out)?;
+        *self = Writer::new(self.padding);
         Ok(())
     }
+}
 
-    pub fn to_vec(&self) -> Vec<u8> {
-        self.cache.to_vec()
-    }
-
-    pub fn to_string(&self) -> Result<String> {
-        String::from_utf8(self.cache.to_vec()).map_err(Error::from)
+impl<W: IoWrite> IoWrite for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.write_bytes(buf).map_err(Into::into)
     }
-}
 
-impl<B> Writer<B> {
-    pub fn new_piped(width: usize, buffer: BufWriter<B>) -> Self {
-        Writer {
-            padding: width,
-            ansi_writer: AnsiWriter::new(buffer),
-            buf: BytesMut::new(),
-            cache: BytesMut::new(),
-            line_len: 0,
-            in_ansi: false,
-            char_buffer: [0; 4],
-        }
+    fn flush(&mut self) -> IoResult<()> {
+        self.ansi_writer.flush_writer().map_err(Into::into)
     }
 }
 
+/// Pads every line of the given bytes to `padding` columns.
+///
+/// ## Errors
+/// - `Error::Utf8` - If `bytes` is not valid utf8
 pub fn pad_bytes(bytes: &[u8], padding: usize) -> Result<Vec<u8>> {
     let mut writer = Writer::new(padding);
     writer.write_bytes(bytes)?;
     writer.close()?;
-    Ok(writer.to_vec())
+    Ok(writer.into_inner())
 }
 
+/// Pads every line of the given string to `padding` columns.
+///
+/// # Examples
+/// ```
+/// # use reflors::padding::pad_string;
+/// assert_eq!(pad_string("one\ntwo2\r\n", 5).unwrap(), "one  \ntwo2 \r\n");
+/// assert_eq!(pad_string("\x1b[31mred\x1b[0m", 5).unwrap(), "\x1b[31mred\x1b[0m  ");
+/// ```
+///
+/// ## Errors
+/// This function does not currently fail.
 pub fn pad_string(string: &str, padding: usize) -> Result<String> {
     let mut writer = Writer::new(padding);
     writer.write_str(string)?;
@@ -0,0 +1,299 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io::{Result as IoResult, Write as IoWrite};
+
+use crate::{fmt::Utf8Decoder, Result};
+
+/// A transformation over chunks of text, such as wrapping, indenting or padding it.
+///
+/// Text is fed to a stage in arbitrarily sized chunks through [`Reflow::write`], and the stage
+/// appends whatever output it can already produce. Any text the stage is still holding back is
+/// written by [`Reflow::finish`], which also resets the stage so it can be reused for the next
+/// text. Escape sequences are passed along with the text, so every stage sees (and keeps track of)
+/// the styles set by the stages before it.
+///
+/// This is implemented by the in-memory writers of the [`dedent`](`crate::dedent`),
+/// [`indent`](`crate::indent`), [`padding`](`crate::padding`), [`truncate`](`crate::truncate`),
+/// [`wordwrap`](`crate::wordwrap`) and [`wrap`](`crate::wrap`) modules, and by [`Pipeline`]
+/// itself.
+pub trait Reflow {
+    /// Transforms a chunk of text, appending the output to `out`.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If a stage produces invalid utf8
+    fn write(&mut self, input: &str, out: &mut String) -> Result<()>;
+
+    /// Finishes the current text, appending any output still being held back to `out`, and
+    /// resets this stage.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If a stage produces invalid utf8
+    fn finish(&mut self, out: &mut String) -> Result<()>;
+}
+
+/// A chain of [`Reflow`] stages, where the output of each stage is the input of the next one.
+///
+/// Chunks are passed through every stage as soon as they are written, so each stage only buffers
+/// what it needs to (e.g. the current word when wrapping).
+///
+/// # Examples
+/// ```
+/// # use reflors::{indent::{self, IndentOptions}, padding, pipeline::Pipeline, wordwrap};
+/// let mut pipeline = Pipeline::new()
+///     .with_stage(wordwrap::Writer::new(10))
+///     .with_stage(indent::Writer::new(IndentOptions::two_spaces()))
+///     .with_stage(padding::Writer::new(14));
+/// assert_eq!(
+///     pipeline.run("Some text to reflow").unwrap(),
+///     "  Some text   \n  to reflow   "
+/// );
+/// // pipelines can be reused
+/// assert_eq!(pipeline.run("More text").unwrap(), "  More text   ");
+/// ```
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Reflow>>,
+}
+
+impl Pipeline {
+    /// Creates a new, empty [`Pipeline`], which passes text through as it is.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a stage to the end of this [`Pipeline`].
+    #[must_use]
+    pub fn with_stage<R: Reflow + 'static>(mut self, stage: R) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Gets the number of stages in this [`Pipeline`].
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Whether this [`Pipeline`] has no stages.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Passes the whole `input` through every stage and finishes it.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If a stage produces invalid utf8
+    pub fn run(&mut self, input: &str) -> Result<String> {
+        let mut output = String::with_capacity(input.len());
+        self.write(input, &mut output)?;
+        self.finish(&mut output)?;
+        Ok(output)
+    }
+
+    /// **Consumes** this [`Pipeline`] and creates a [`Writer`] that streams its output to
+    /// `writer`.
+    #[must_use]
+    pub fn into_writer<W>(self, writer: W) -> Writer<Self, W> {
+        Writer::new(self, writer)
+    }
+}
+
+impl Reflow for Pipeline {
+    fn write(&mut self, input: &str, out: &mut String) -> Result<()> {
+        let mut chunk = input.to_string();
+        for stage in &mut self.stages {
+            let mut output = String::with_capacity(chunk.len());
+            stage.write(&chunk, &mut output)?;
+            chunk = output;
+        }
+
+        out.push_str(&chunk);
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut String) -> Result<()> {
+        // whatever a stage was holding back still has to go through the stages after it
+        let mut chunk = String::new();
+        for stage in &mut self.stages {
+            let mut output = String::new();
+            if !chunk.is_empty() {
+                stage.write(&chunk, &mut output)?;
+            }
+            stage.finish(&mut output)?;
+            chunk = output;
+        }
+
+        out.push_str(&chunk);
+        Ok(())
+    }
+}
+
+/// A [`std::io::Write`] that passes everything written to it through a [`Reflow`] stage (such as
+/// a [`Pipeline`]) and writes the output to an underlying writer.
+pub struct Writer<R, W> {
+    reflow: R,
+    inner: W,
+    buf: String,
+    decoder: Utf8Decoder,
+}
+
+impl<R, W> Writer<R, W> {
+    /// Creates a new [`Writer`] passing text through `reflow` and writing it to `writer`.
+    #[must_use]
+    pub fn new(reflow: R, writer: W) -> Self {
+        Self {
+            reflow,
+            inner: writer,
+            buf: String::new(),
+            decoder: Utf8Decoder::default(),
+        }
+    }
+
+    /// **Consumes** this [`Writer`] and returns the underlying writer, without
+    /// [closing](`Writer::close`) it.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<R: Reflow, W: IoWrite> Writer<R, W> {
+    /// Write the given bytes to this [`Writer`]. A utf8 character may be split across writes, its
+    /// start is held back until the rest of it is written.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If `bytes` can't be valid utf8
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
+        let s = self.decoder.decode(bytes)?;
+        self.write_str(&s)?;
+        Ok(bytes.len())
+    }
+
+    /// Write the given string to this [`Writer`].
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn write_str(&mut self, s: &str) -> Result<usize> {
+        self.reflow.write(s, &mut self.buf)?;
+        self.flush_buf()?;
+        Ok(s.len())
+    }
+
+    /// Finishes the text written so far and flushes the underlying writer.
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn close(&mut self) -> Result<()> {
+        self.reflow.finish(&mut self.buf)?;
+        self.flush_buf()?;
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    fn flush_buf(&mut self) -> Result<()> {
+        self.inner.write_all(self.buf.as_bytes())?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<R: Reflow, W: IoWrite> IoWrite for Writer<R, W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.write_bytes(buf).map_err(Into::into)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+/// Moves the output a writer has written to its internal buffer into `out`.
+pub(crate) fn drain(buf: &mut Vec<u8>, out: &mut String) -> Result<()> {
+    out.push_str(std::str::from_utf8(buf)?);
+    buf.clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dedent::{self, DedentOptions},
+        indent::{self, IndentOptions},
+        padding, truncate, wordwrap,
+    };
+    use pretty_assertions::assert_eq;
+
+    fn pipeline() -> Pipeline {
+        Pipeline::new()
+            .with_stage(dedent::Writer::new(Vec::new(), DedentOptions::indoc()))
+            .with_stage(wordwrap::Writer::new(12))
+            .with_stage(indent::Writer::new(IndentOptions::four_spaces()))
+            .with_stage(padding::Writer::new(18))
+    }
+
+    #[test]
+    fn stages() {
+        let text = "\n    The quick \x1b[31mbröwn föx\x1b[0m jumps\n      över the lazy 犬";
+        let expected = padding::pad_string(
+            &indent::indent_str(
+                &wordwrap::wordwrap_str(
+                    &dedent::dedent_with(text, DedentOptions::indoc()).unwrap(),
+                    &wordwrap::WrapOptions::new(12),
+                )
+                .unwrap(),
+                IndentOptions::four_spaces(),
+                false,
+            ),
+            18,
+        )
+        .unwrap();
+
+        let mut pipeline = pipeline();
+        assert_eq!(pipeline.len(), 4);
+        assert_eq!(pipeline.run(text).unwrap(), expected);
+        // finishing resets every stage, so the pipeline can be reused
+        assert_eq!(pipeline.run(text).unwrap(), expected);
+
+        // chunks can be of any size, even if they split characters
+        for size in 1..text.len() {
+            let mut writer = Writer::new(self::pipeline(), Vec::new());
+            for chunk in text.as_bytes().chunks(size) {
+                assert_eq!(writer.write_bytes(chunk).unwrap(), chunk.len());
+            }
+            writer.close().unwrap();
+            assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn nested() {
+        let mut pipeline = Pipeline::new()
+            .with_stage(Pipeline::new().with_stage(truncate::Writer::with_ending(8, ".")))
+            .with_stage(Pipeline::new());
+        assert_eq!(
+            pipeline.run("\x1b[1mtruncated text\x1b[0m").unwrap(),
+            "\x1b[1mtruncat.\x1b[0m"
+        );
+        assert_eq!(Pipeline::new().run("as is").unwrap(), "as is");
+    }
+
+    #[test]
+    fn writer() {
+        use std::io::Write;
+
+        let mut writer = Pipeline::new()
+            .with_stage(wordwrap::Writer::new(5))
+            .with_stage(indent::Writer::new(IndentOptions::two_spaces()))
+            .into_writer(Vec::new());
+        writer.write_all(b"one two three").unwrap();
+        writer.close().unwrap();
+        assert_eq!(writer.into_inner(), b"  one\n  two\n  three");
+    }
+}
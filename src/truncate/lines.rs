@@ -0,0 +1,239 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io::Sink;
+
+use crate::{ansi::writer::Writer as AnsiWriter, Result};
+
+use super::{truncate_with, TruncateMode, DEFAULT_TAIL};
+
+/// The default line written in place of the lines that were dropped, where `{}` is replaced with
+/// the number of dropped lines.
+pub const DEFAULT_INDICATOR: &str = "… {} more lines";
+
+/// The default line written in place of a single dropped line (see [`DEFAULT_INDICATOR`]).
+pub const DEFAULT_SINGULAR_INDICATOR: &str = "… {} more line";
+
+const RESET: &str = "\x1b[0m";
+
+/// Options for truncating text to a maximum number of lines with [`truncate_lines`], and
+/// optionally each of those lines to a maximum width.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineOptions {
+    /// The maximum number of lines kept.
+    max_lines: usize,
+    /// The line written in place of the dropped lines, `{}` is replaced with their number.
+    indicator: String,
+    /// The line written in place of a single dropped line.
+    singular_indicator: String,
+    /// The escape sequences used to style the indicator, if any.
+    indicator_style: String,
+    /// The maximum **visible** width of a line, if lines are truncated at all.
+    width: Option<usize>,
+    /// The tail written in place of the end of a line that is too wide.
+    tail: String,
+    /// Which part of a line that is too wide is dropped.
+    mode: TruncateMode,
+}
+
+impl LineOptions {
+    /// Creates a new [`LineOptions`] keeping at most `max_lines` lines, using the
+    /// [`DEFAULT_INDICATOR`] (or [`DEFAULT_SINGULAR_INDICATOR`]) and not truncating the width of
+    /// the lines.
+    #[must_use]
+    pub fn new(max_lines: usize) -> Self {
+        Self {
+            max_lines,
+            indicator: DEFAULT_INDICATOR.to_string(),
+            singular_indicator: DEFAULT_SINGULAR_INDICATOR.to_string(),
+            indicator_style: String::new(),
+            width: None,
+            tail: DEFAULT_TAIL.to_string(),
+            mode: TruncateMode::End,
+        }
+    }
+
+    /// Sets the maximum number of lines kept.
+    #[must_use]
+    pub fn with_max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    /// Sets the line written in place of the dropped lines, where `{}` is replaced with their
+    /// number. This is used for a single dropped line as well, unless a
+    /// [singular indicator](`LineOptions::with_singular_indicator`) is set afterwards.
+    #[must_use]
+    pub fn with_indicator(mut self, indicator: &str) -> Self {
+        self.indicator = indicator.to_string();
+        self.singular_indicator = indicator.to_string();
+        self
+    }
+
+    /// Sets the line written in place of a single dropped line, where `{}` is replaced with `1`.
+    #[must_use]
+    pub fn with_singular_indicator(mut self, indicator: &str) -> Self {
+        self.singular_indicator = indicator.to_string();
+        self
+    }
+
+    /// Sets the escape sequences used to style the indicator (e.g. `"\x1b[2m"` to dim it). The
+    /// style is reset after the indicator.
+    #[must_use]
+    pub fn with_indicator_style(mut self, style: &str) -> Self {
+        self.indicator_style = style.to_string();
+        self
+    }
+
+    /// Sets the maximum **visible** width of every kept line (and of the indicator), so the text is
+    /// clipped to `width` × `max_lines` columns.
+    #[must_use]
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Sets the tail written in place of the end of a line that is too wide.
+    #[must_use]
+    pub fn with_tail(mut self, tail: &str) -> Self {
+        self.tail = tail.to_string();
+        self
+    }
+
+    /// Sets which part of a line that is too wide is dropped.
+    #[must_use]
+    pub fn with_mode(mut self, mode: TruncateMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+impl LineOptions {
+    /// Gets the maximum number of lines kept.
+    #[must_use]
+    pub const fn max_lines(&self) -> usize {
+        self.max_lines
+    }
+
+    /// Gets the line written in place of the dropped lines.
+    #[must_use]
+    pub fn indicator(&self) -> &str {
+        self.indicator.as_str()
+    }
+
+    /// Gets the line written in place of a single dropped line.
+    #[must_use]
+    pub fn singular_indicator(&self) -> &str {
+        self.singular_indicator.as_str()
+    }
+
+    /// Gets the escape sequences used to style the indicator.
+    #[must_use]
+    pub fn indicator_style(&self) -> &str {
+        self.indicator_style.as_str()
+    }
+
+    /// Gets the maximum **visible** width of a line, if lines are truncated at all.
+    #[must_use]
+    pub const fn width(&self) -> Option<usize> {
+        self.width
+    }
+
+    /// Gets the tail written in place of the end of a line that is too wide.
+    #[must_use]
+    pub fn tail(&self) -> &str {
+        self.tail.as_str()
+    }
+
+    /// Gets which part of a line that is too wide is dropped.
+    #[must_use]
+    pub const fn mode(&self) -> TruncateMode {
+        self.mode
+    }
+
+    /// Truncates a single line to the width, if any.
+    fn clip(&self, line: &str) -> Result<String> {
+        match self.width {
+            Some(width) => truncate_with(line, width, &self.tail, self.mode),
+            None => Ok(line.to_string()),
+        }
+    }
+}
+
+impl Default for LineOptions {
+    fn default() -> Self {
+        Self::new(usize::MAX)
+    }
+}
+
+/// Truncates the given string to [`LineOptions::max_lines`] lines, replacing the lines after them
+/// with the [`LineOptions::indicator`] (or the [`LineOptions::singular_indicator`] if only one line
+/// is dropped).
+///
+/// Every kept line is self-contained: the styles still active at its end are reset, and restored
+/// at the start of the next line.
+///
+/// # Examples
+/// ```
+/// # use reflors::truncate::{truncate_lines, LineOptions};
+/// let text = "\x1b[31mone\ntwo\x1b[0m\nthree\nfour";
+/// assert_eq!(
+///     truncate_lines(text, &LineOptions::new(2)).unwrap(),
+///     "\x1b[31mone\x1b[0m\n\x1b[31mtwo\x1b[0m\n… 2 more lines"
+/// );
+/// assert_eq!(
+///     truncate_lines(
+///         text,
+///         &LineOptions::new(1).with_width(2).with_tail("").with_indicator("+{} lines")
+///     )
+///     .unwrap(),
+///     "\x1b[31mon\x1b[0m\n+3"
+/// );
+/// ```
+///
+/// ## Errors
+/// This function does not currently fail.
+pub fn truncate_lines(input: &str, options: &LineOptions) -> Result<String> {
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let kept = lines.len().min(options.max_lines);
+    let mut output = String::with_capacity(input.len());
+    // keeps track of the styles active at the start of each line
+    let mut tracker = AnsiWriter::<Sink>::new(std::io::sink());
+
+    for line in &lines[..kept] {
+        let content = line.strip_suffix('\n').unwrap_or(line);
+        let content = content.strip_suffix('\r').unwrap_or(content);
+        let ending = &line[content.len()..];
+
+        let mut styled = tracker.last_sequence()?.to_string();
+        styled.push_str(content);
+        tracker.write_str(content)?;
+
+        let clipped = options.clip(&styled)?;
+        output.push_str(&clipped);
+        if !tracker.last_sequence()?.is_empty() && !clipped.ends_with(RESET) {
+            output.push_str(RESET);
+        }
+        output.push_str(ending);
+    }
+
+    let dropped = lines.len() - kept;
+    if dropped > 0 {
+        let template = if dropped == 1 {
+            &options.singular_indicator
+        } else {
+            &options.indicator
+        };
+        let mut indicator = options.indicator_style.clone();
+        indicator.push_str(&template.replace("{}", &dropped.to_string()));
+        if !options.indicator_style.is_empty() {
+            indicator.push_str(RESET);
+        }
+        output.push_str(&options.clip(&indicator)?);
+    }
+
+    Ok(output)
+}
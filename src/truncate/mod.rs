@@ -4,4 +4,213 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+mod lines;
+mod mode;
 mod writer;
+pub use lines::{truncate_lines, LineOptions, DEFAULT_INDICATOR, DEFAULT_SINGULAR_INDICATOR};
+pub use mode::TruncateMode;
+pub use writer::{truncate_string, truncate_with, Writer, DEFAULT_PAD, DEFAULT_TAIL};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fmt::FmtWriter;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn truncate() {
+        assert_eq!(truncate_string("foobar", 3, "").unwrap(), "foo");
+        assert_eq!(truncate_string("foobar", 4, ".").unwrap(), "foo.");
+        assert_eq!(truncate_string("foobar", 6, "").unwrap(), "foobar");
+        assert_eq!(truncate_string("foobar", 6, "...").unwrap(), "foobar");
+        assert_eq!(truncate_string("foobar", 2, "...").unwrap(), "...");
+        assert_eq!(truncate_string("", 2, "...").unwrap(), "");
+    }
+
+    #[test]
+    fn ansi() {
+        assert_eq!(
+            truncate_string("\x1b[38;5;1mfoo\x1b[0mbar", 5, ".").unwrap(),
+            "\x1b[38;5;1mfoo\x1b[0mb."
+        );
+        assert_eq!(
+            truncate_string("\x1b[31mfoo\x1b[1mbar\x1b[0m", 4, "").unwrap(),
+            "\x1b[31mfoo\x1b[1mb\x1b[0m"
+        );
+        // sequences are kept if the text fits after all
+        assert_eq!(
+            truncate_string("\x1b[31mfoobar\x1b[0m", 6, "...").unwrap(),
+            "\x1b[31mfoobar\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        // the url doesn't count towards the width
+        assert_eq!(truncate_string(link, 4, ".").unwrap(), link);
+    }
+
+    #[test]
+    fn modes() {
+        let path = "long/path/file.rs";
+        assert_eq!(
+            truncate_with(path, 12, ".", TruncateMode::End).unwrap(),
+            truncate_string(path, 12, ".").unwrap()
+        );
+        assert_eq!(
+            truncate_with(path, 12, ".", TruncateMode::Start).unwrap(),
+            ".ath/file.rs"
+        );
+        assert_eq!(
+            truncate_with(path, 12, ".", TruncateMode::MIDDLE).unwrap(),
+            "long/p.le.rs"
+        );
+        assert_eq!(
+            truncate_with(path, 12, ".", TruncateMode::Middle(0.2)).unwrap(),
+            "lo.h/file.rs"
+        );
+        assert_eq!(
+            truncate_with(path, 12, ".", TruncateMode::Middle(1.0)).unwrap(),
+            "long/path/f."
+        );
+        for mode in [TruncateMode::Start, TruncateMode::MIDDLE] {
+            assert_eq!(truncate_with(path, 17, ".", mode).unwrap(), path);
+            assert_eq!(truncate_with(path, 1, "..", mode).unwrap(), "..");
+        }
+
+        // tabs extend to the next tab stop
+        assert_eq!(
+            truncate_with("\tab", 10, ".", TruncateMode::Start).unwrap(),
+            "\tab"
+        );
+        assert_eq!(
+            truncate_with("\tab", 9, ".", TruncateMode::Start).unwrap(),
+            ".      ab"
+        );
+        assert_eq!(TruncateMode::default(), TruncateMode::End);
+    }
+
+    #[test]
+    fn modes_ansi() {
+        let text = "\x1b[31mred\x1b[0m plain \x1b[1mbold\x1b[0m";
+        assert_eq!(
+            truncate_with(text, 5, ".", TruncateMode::Start).unwrap(),
+            ".\x1b[31m\x1b[0m\x1b[1mbold\x1b[0m"
+        );
+        // the styles of the dropped part still apply to the kept part after the tail
+        assert_eq!(
+            truncate_with("\x1b[31mredder\x1b[0m", 4, ".", TruncateMode::Start).unwrap(),
+            ".\x1b[31mder\x1b[0m"
+        );
+        assert_eq!(
+            truncate_with(text, 7, ".", TruncateMode::MIDDLE).unwrap(),
+            "\x1b[31mred\x1b[0m.\x1b[1mold\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn wide() {
+        let text = "東京タワー";
+        assert_eq!(truncate_string(text, 10, "").unwrap(), text);
+        assert_eq!(truncate_string(text, 5, "").unwrap(), "東京 ");
+        assert_eq!(truncate_string(text, 6, "…").unwrap(), "東京 …");
+        assert_eq!(truncate_string(text, 7, "…").unwrap(), "東京タ…");
+        let mut writer = Writer::with_ending(4, "|").with_pad('_');
+        writer.write_str("\x1b[1m東京タ\x1b[0m").unwrap();
+        assert_eq!(writer.into_string().unwrap(), "\x1b[1m東_|\x1b[0m");
+
+        assert_eq!(
+            truncate_with(text, 6, "…", TruncateMode::Start).unwrap(),
+            "… ワー"
+        );
+        assert_eq!(
+            truncate_with(text, 6, "…", TruncateMode::MIDDLE).unwrap(),
+            "東 …ー"
+        );
+        assert_eq!(
+            truncate_with("a東京b", 5, "…", TruncateMode::MIDDLE).unwrap(),
+            "a … b"
+        );
+    }
+
+    #[test]
+    fn lines() {
+        let text = "one\ntwo\r\nthree\nfour\n";
+        assert_eq!(truncate_lines(text, &LineOptions::new(4)).unwrap(), text);
+        assert_eq!(truncate_lines(text, &LineOptions::default()).unwrap(), text);
+        assert_eq!(
+            truncate_lines(text, &LineOptions::new(2)).unwrap(),
+            "one\ntwo\r\n… 2 more lines"
+        );
+        assert_eq!(
+            truncate_lines(text, &LineOptions::new(0).with_indicator("({} lines)")).unwrap(),
+            "(4 lines)"
+        );
+        assert_eq!(truncate_lines("", &LineOptions::new(0)).unwrap(), "");
+
+        // a single dropped line isn't plural
+        assert_eq!(
+            truncate_lines(text, &LineOptions::new(3)).unwrap(),
+            "one\ntwo\r\nthree\n… 1 more line"
+        );
+        let options = LineOptions::new(3).with_indicator("({} lines)");
+        assert_eq!(
+            truncate_lines(text, &options).unwrap(),
+            "one\ntwo\r\nthree\n(1 lines)"
+        );
+        let options = options.with_singular_indicator("({} line)");
+        assert_eq!(
+            truncate_lines(text, &options).unwrap(),
+            "one\ntwo\r\nthree\n(1 line)"
+        );
+    }
+
+    #[test]
+    fn lines_ansi() {
+        let text = "\x1b[1mbold \x1b[31mred\nstill red\x1b[0m\nplain\nmore";
+        let options = LineOptions::new(2)
+            .with_indicator("+{}")
+            .with_indicator_style("\x1b[2m");
+        assert_eq!(
+            truncate_lines(text, &options).unwrap(),
+            "\x1b[1mbold \x1b[31mred\x1b[0m\n\x1b[1m\x1b[31mstill red\x1b[0m\n\x1b[2m+2\x1b[0m"
+        );
+
+        // lines can be truncated to a width as well
+        let options = options.with_width(6).with_tail(".");
+        assert_eq!(
+            truncate_lines(text, &options).unwrap(),
+            "\x1b[1mbold \x1b[31m.\x1b[0m\n\x1b[1m\x1b[31mstill.\x1b[0m\n\x1b[2m+2\x1b[0m"
+        );
+        let options = options.with_max_lines(3).with_mode(TruncateMode::Start);
+        assert_eq!(
+            truncate_lines(text, &options).unwrap(),
+            ".\x1b[1md \x1b[31mred\x1b[0m\n.\x1b[1m\x1b[31ml red\x1b[0m\nplain\n\x1b[2m+1\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn piped() {
+        let text = "\x1b[1m東京\x1b[0m text that is long";
+        let expected = truncate_string(text, 10, "...").unwrap();
+        assert_eq!(expected, "\x1b[1m東京\x1b[0m te...");
+        for size in 1..text.len() {
+            let mut writer = Writer::new_piped(10, "...", FmtWriter::new(String::new()));
+            for chunk in text.as_bytes().chunks(size) {
+                assert_eq!(writer.write_bytes(chunk).unwrap(), chunk.len());
+            }
+            writer.close().unwrap();
+            assert!(writer.is_truncated());
+            assert_eq!(writer.into_inner().into_inner(), expected);
+        }
+
+        let expected = truncate_with(text, 10, "...", TruncateMode::Start).unwrap();
+        let mut writer = Writer::new(10).with_mode(TruncateMode::Start);
+        for chunk in text.as_bytes().chunks(2) {
+            writer.write_bytes(chunk).unwrap();
+        }
+        assert!(!writer.is_truncated());
+        assert_eq!(writer.into_string().unwrap(), expected);
+    }
+}
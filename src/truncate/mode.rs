@@ -0,0 +1,108 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::{
+    ansi,
+    iter::{Token, Tokens},
+};
+
+/// Which part of the text is dropped when truncating it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TruncateMode {
+    /// Keeps the start of the text and replaces its end with the tail (`very_long_fi…`).
+    #[default]
+    End,
+    /// Keeps the end of the text and replaces its start with the tail (`…long/path/file.rs`).
+    Start,
+    /// Keeps both ends of the text and replaces its middle with the tail (`very_long…_name.rs`).
+    ///
+    /// The value is the fraction (between `0.0` and `1.0`) of the kept columns that go before the
+    /// tail, so `0.5` splits them evenly and `0.25` favors the end of the text.
+    Middle(f32),
+}
+
+impl TruncateMode {
+    /// Truncates the middle of the text, splitting the kept columns evenly around the tail.
+    pub const MIDDLE: Self = Self::Middle(0.5);
+
+    /// Gets the number of columns kept before and after the tail, out of `keep` columns.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    pub(crate) fn split(self, keep: usize) -> (usize, usize) {
+        match self {
+            Self::End => (keep, 0),
+            Self::Start => (0, keep),
+            Self::Middle(ratio) => {
+                let head = (keep as f32 * ratio.clamp(0.0, 1.0)).round() as usize;
+                (head, keep - head)
+            }
+        }
+    }
+}
+
+/// Keeps the first `head` and the last `tail` visible columns of `input`, which is `total`
+/// columns wide, and writes `ending` in place of the columns in between.
+///
+/// The escape sequences of the dropped columns are written after the `ending`, so the kept columns
+/// after it are styled the same way they were before. A wide character straddling either cut is
+/// dropped, and the column it leaves empty is filled with `pad`.
+pub(crate) fn cut(
+    input: &str,
+    total: usize,
+    (head, tail): (usize, usize),
+    ending: &str,
+    pad: char,
+) -> String {
+    let tail_start = total.saturating_sub(tail).max(head);
+    let mut output = String::with_capacity(input.len());
+    let mut dropped_sequences = String::new();
+    let mut column = 0;
+    // with nothing to keep before the ending, even the leading sequences go after it
+    let mut dropping = head == 0;
+    let mut ending_written = false;
+
+    for token in Tokens::new(input) {
+        let text = match token {
+            Token::Text(text) => text,
+            Token::Escape { raw, .. } => {
+                if dropping && !ending_written {
+                    dropped_sequences.push_str(raw);
+                } else {
+                    output.push_str(raw);
+                }
+                continue;
+            }
+        };
+
+        for ch in text.chars() {
+            let width = ansi::char_width_at(ch, column);
+            if !dropping && column + width > head {
+                dropping = true;
+                output.extend(std::iter::repeat_n(pad, head - column));
+            }
+            if dropping && !ending_written && width > 0 && column >= tail_start {
+                output.push_str(ending);
+                output.extend(std::iter::repeat_n(pad, column - tail_start));
+                output.push_str(&dropped_sequences);
+                ending_written = true;
+            }
+            column += width;
+            if !dropping || ending_written {
+                output.push(ch);
+            }
+        }
+    }
+
+    if !ending_written {
+        output.push_str(ending);
+        output.push_str(&dropped_sequences);
+    }
+
+    output
+}
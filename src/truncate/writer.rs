@@ -4,63 +4,338 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use bytes::{buf::Writer as BufWriter, BufMut, Bytes, BytesMut};
+use std::io::{Result as IoResult, Write as IoWrite};
 
 use crate::{
-    ansi::{self, buffer::Buffer as AnsiBuffer, writer::Writer as AnsiWriter},
+    ansi::{self, writer::Writer as AnsiWriter},
+    fmt::Utf8Decoder,
+    iter::{EscapeBuffer, Token, Tokens},
+    pipeline::{self, Reflow},
     Error, Result,
 };
 
-pub struct Writer {
+use super::mode::{self, TruncateMode};
+
+/// The default tail appended to truncated text.
+pub const DEFAULT_TAIL: &str = "...";
+
+/// The default character filling the gap left by a wide character that didn't fit.
+pub const DEFAULT_PAD: char = ' ';
+
+/// A streaming, ansi-aware truncating writer. Text is written until it would exceed `width`
+/// visible columns, at which point the rest is dropped and the tail is written instead (so the
+/// output, tail included, fits within `width` unless the tail itself doesn't).
+///
+/// The last few columns that would be replaced by the tail are held back until it's known whether
+/// the text is truncated at all, so remember to [`close`](`Writer::close`) the writer.
+///
+/// A wide character straddling the cut is dropped as a whole, and the column it leaves empty is
+/// filled with the [pad](`Writer::with_pad`) character, so truncated text is always exactly
+/// `width` columns wide.
+///
+/// Text can also be truncated at its start or in its middle by choosing a different
+/// [`TruncateMode`], in which case the whole text is held back until the writer is closed.
+///
+/// Source: [muesli/reflow/truncate/truncate.go](https://github.com/muesli/reflow/blob/master/truncate/truncate.go)
+pub struct Writer<W> {
     width: usize,
     tail: String,
-    ansi_writer: AnsiWriter<BytesMut>,
-    buf: AnsiBuffer,
-    is_ansi: bool,
+    mode: TruncateMode,
+    pad: char,
+    ansi_writer: AnsiWriter<W>,
+    held: String,
+    line_len: usize,
+    written: usize,
+    escapes: EscapeBuffer,
+    truncated: bool,
+    decoder: Utf8Decoder,
 }
 
-impl Writer {
+impl Writer<Vec<u8>> {
+    /// Creates a new [`Writer`] that truncates at `width` using the [`DEFAULT_TAIL`] and an
+    /// internal buffer.
+    #[must_use]
     pub fn new(width: usize) -> Self {
-        Self {
-            width,
-            tail: String::from("..."),
-            ansi_writer: AnsiWriter::new(BytesMut::new().writer()),
-            buf: AnsiBuffer::new(),
-            is_ansi: false,
-        }
+        Self::with_ending(width, DEFAULT_TAIL)
     }
 
+    /// Creates a new [`Writer`] that truncates at `width` using the given `tail` and an internal
+    /// buffer.
+    #[must_use]
     pub fn with_ending(width: usize, tail: &str) -> Self {
+        Self::new_piped(width, tail, Vec::new())
+    }
+
+    /// [Closes](`Writer::close`) this [`Writer`] and tries to create a string from its internal
+    /// buffer.
+    ///
+    /// ## Errors
+    /// - `crate::Error::Utf8` if the buffer is not a valid utf8 string
+    pub fn into_string(mut self) -> Result<String> {
+        self.close()?;
+        self.ansi_writer.into_string()
+    }
+}
+
+impl<W> Writer<W> {
+    /// Creates a new [`Writer`] that truncates at `width` using the given `tail`, and writes to
+    /// `writer`.
+    #[must_use]
+    pub fn new_piped(width: usize, tail: &str, writer: W) -> Self {
         Self {
             width,
             tail: tail.to_string(),
-            ansi_writer: AnsiWriter::new(BytesMut::new().writer()),
-            buf: AnsiBuffer::new(),
-            is_ansi: false,
+            mode: TruncateMode::End,
+            pad: DEFAULT_PAD,
+            ansi_writer: AnsiWriter::new(writer),
+            held: String::new(),
+            line_len: 0,
+            written: 0,
+            escapes: EscapeBuffer::default(),
+            truncated: false,
+            decoder: Utf8Decoder::default(),
         }
     }
 
+    /// Sets which part of the text is dropped when truncating it.
+    #[must_use]
+    pub fn with_mode(mut self, mode: TruncateMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the character filling the gap left by a wide character that didn't fit.
+    #[must_use]
+    pub fn with_pad(mut self, pad: char) -> Self {
+        self.pad = pad;
+        self
+    }
+
+    /// Gets the width text is truncated at.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Gets the tail appended to truncated text.
+    #[must_use]
+    pub fn tail(&self) -> &str {
+        self.tail.as_str()
+    }
+
+    /// Gets which part of the text is dropped when truncating it.
+    #[must_use]
+    pub fn mode(&self) -> TruncateMode {
+        self.mode
+    }
+
+    /// Gets the character filling the gap left by a wide character that didn't fit.
+    #[must_use]
+    pub fn pad(&self) -> char {
+        self.pad
+    }
+
+    /// Whether the text written so far has been truncated. Unless the [`TruncateMode::End`] is
+    /// used, this is only known once the writer is [closed](`Writer::close`).
+    #[must_use]
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// **Consumes** this [`Writer`] and returns the underlying writer, without
+    /// [closing](`Writer::close`) it.
+    pub fn into_inner(self) -> W {
+        self.ansi_writer.into_inner()
+    }
+}
+
+impl<W: IoWrite> Writer<W> {
+    /// Write the given bytes to this [`Writer`]. A utf8 character may be split across writes, its
+    /// start is held back until the rest of it is written.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If `bytes` can't be valid utf8
+    /// - `Error::Io` - If writing to the underlying writer fails
     pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
-        let s = std::str::from_utf8(bytes)?;
-        self.write_str(s)
+        let s = self.decoder.decode(bytes)?;
+        self.write_str(&s)?;
+        Ok(bytes.len())
     }
 
+    /// Write the given string to this [`Writer`].
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
     pub fn write_str(&mut self, s: &str) -> Result<usize> {
-        let tw = ansi::visible_width(self.tail.as_str());
-        if self.width < tw {
-            self.buf.push_str(self.tail.as_str());
-            return Ok(tw);
+        if self.mode != TruncateMode::End {
+            // the kept columns depend on the width of the whole text
+            self.held.push_str(s);
+            return Ok(s.len());
         }
 
-        let target_width = self.width - tw;
-        let mut current = 0;
-        for ch in s.chars() {}
+        let target = self
+            .width
+            .saturating_sub(ansi::visible_width(self.tail.as_str()));
+        let mut char_buffer = [0; 4];
 
-        Ok(0)
+        let input = self.escapes.complete(s);
+        for token in Tokens::new(&input) {
+            if self.truncated {
+                break;
+            }
+
+            let text = match token {
+                Token::Text(text) => text,
+                Token::Escape { raw, .. } => {
+                    if self.held.is_empty() {
+                        self.ansi_writer.write_str(raw)?;
+                    } else {
+                        self.held.push_str(raw);
+                    }
+                    continue;
+                }
+            };
+
+            for ch in text.chars() {
+                self.line_len += ansi::char_width_at(ch, self.line_len);
+                if self.line_len > self.width {
+                    self.truncate()?;
+                    break;
+                }
+                if self.line_len > target {
+                    // this may still fit, if nothing else follows it
+                    self.held.push(ch);
+                    continue;
+                }
+                self.written = self.line_len;
+
+                if self.held.is_empty() {
+                    self.ansi_writer
+                        .write_str(ch.encode_utf8(&mut char_buffer))?;
+                } else {
+                    self.held.push(ch);
+                }
+            }
+        }
+
+        Ok(s.len())
+    }
+
+    /// Finishes truncating by writing the text held back (if it wasn't truncated after all) and
+    /// flushing the underlying writer.
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn close(&mut self) -> Result<()> {
+        if self.mode != TruncateMode::End {
+            let text = std::mem::take(&mut self.held);
+            let total = ansi::visible_width(&text);
+            if total > self.width {
+                let keep = self
+                    .width
+                    .saturating_sub(ansi::visible_width(self.tail.as_str()));
+                let (head, tail) = self.mode.split(keep);
+                self.truncated = true;
+                let cut = mode::cut(&text, total, (head, tail), &self.tail, self.pad);
+                self.ansi_writer.write_str(&cut)?;
+            } else {
+                self.ansi_writer.write_str(&text)?;
+            }
+        } else if !self.held.is_empty() {
+            let held = std::mem::take(&mut self.held);
+            self.ansi_writer.write_str(&held)?;
+        }
+
+        self.ansi_writer.flush_writer()
+    }
+
+    /// Drops the text held back and writes the tail instead, padding the columns left empty by a
+    /// wide character that was dropped before it.
+    fn truncate(&mut self) -> Result<()> {
+        self.held.clear();
+        self.truncated = true;
+        let tail_width = ansi::visible_width(self.tail.as_str());
+        for _ in (self.written + tail_width)..self.width {
+            self.ansi_writer
+                .write_raw(self.pad.encode_utf8(&mut [0; 4]).as_bytes())?;
+        }
+        self.ansi_writer.write_raw(self.tail.as_bytes())?;
+        if !self.ansi_writer.last_sequence()?.is_empty() {
+            self.ansi_writer.reset_ansi()?;
+        }
+
+        Ok(())
     }
 }
 
+impl Reflow for Writer<Vec<u8>> {
+    fn write(&mut self, input: &str, out: &mut String) -> Result<()> {
+        self.write_str(input)?;
+        pipeline::drain(self.ansi_writer.get_mut(), out)
+    }
+
+    fn finish(&mut self, out: &mut String) -> Result<()> {
+        self.close()?;
+        pipeline::drain(self.ansi_writer.get_mut(), out)?;
+        *self = Writer::with_ending(self.width, &self.tail)
+            .with_mode(self.mode)
+            .with_pad(self.pad);
+        Ok(())
+    }
+}
+
+impl<W: IoWrite> IoWrite for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.write_bytes(buf).map_err(Into::into)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.ansi_writer.flush_writer().map_err(Into::into)
+    }
+}
+
+/// Truncates the given string to `width` visible columns, appending `ending` if it had to be
+/// truncated.
+///
+/// # Examples
+/// ```
+/// # use reflors::truncate::truncate_string;
+/// assert_eq!(truncate_string("foobar", 5, "...").unwrap(), "fo...");
+/// assert_eq!(truncate_string("foobar", 6, "...").unwrap(), "foobar");
+/// assert_eq!(truncate_string("\x1b[31mfoobar", 4, ".").unwrap(), "\x1b[31mfoo.\x1b[0m");
+/// ```
+///
+/// ## Errors
+/// This function does not currently fail.
 pub fn truncate_string(input: &str, width: usize, ending: &str) -> Result<String> {
-    let mut writer = Writer::with_ending(width, ending);
-    todo!();
+    truncate_with(input, width, ending, TruncateMode::End)
+}
+
+/// Truncates the given string to `width` visible columns, dropping the part chosen by `mode` and
+/// writing `ending` in its place if it had to be truncated. The escape sequences of the dropped
+/// part are kept, so the remaining text keeps its styles.
+///
+/// # Examples
+/// ```
+/// # use reflors::truncate::{truncate_with, TruncateMode};
+/// let path = "src/truncate/writer.rs";
+/// assert_eq!(truncate_with(path, 12, "..", TruncateMode::Start).unwrap(), "../writer.rs");
+/// assert_eq!(truncate_with(path, 12, "..", TruncateMode::MIDDLE).unwrap(), "src/t..er.rs");
+/// assert_eq!(
+///     truncate_with("\x1b[1mbold\x1b[0m text", 7, ".", TruncateMode::Start).unwrap(),
+///     ".\x1b[1md\x1b[0m text"
+/// );
+/// ```
+///
+/// ## Errors
+/// This function does not currently fail.
+pub fn truncate_with(
+    input: &str,
+    width: usize,
+    ending: &str,
+    mode: TruncateMode,
+) -> Result<String> {
+    let mut writer = Writer::with_ending(width, ending).with_mode(mode);
+    writer.write_str(input)?;
+    writer.into_string()
 }
@@ -0,0 +1,200 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Source-code comment reflowing, similar to vim's `gq`.
+//!
+//! The shared indentation of the comment is detected (see [`dedent`](`crate::dedent`)) and
+//! removed, then the comment prefix shared by every line is detected and stripped. The opening and
+//! closing lines of block comments (`/**` and `*/`) are kept as they are. What remains is
+//! reflowed as markdown (doc comments are usually markdown anyway) to a width that leaves room for
+//! the indentation and prefix, which are then re-applied to every line.
+
+use crate::{
+    ansi,
+    dedent::{self, DedentOptions},
+    indent::IndentOptions,
+    Result,
+};
+
+use super::{markdown, WrapOptions};
+
+/// The comment prefixes that are recognized, longer prefixes must come before any prefix they
+/// start with.
+pub const COMMENT_PREFIXES: &[&str] = &["///", "//!", "//", "#", "--", "*", ">"];
+
+/// Reflows the given source-code comment. See [`wordwrap_comment_str`](`super::wordwrap_comment_str`).
+pub(super) fn reflow(input: &str, options: &WrapOptions) -> Result<String> {
+    let lines = input.lines().collect::<Vec<_>>();
+
+    // the opening and closing lines of block comments are kept as they are
+    let opening = lines
+        .first()
+        .filter(|line| matches!(line.trim(), "/*" | "/**" | "/*!"));
+    let closing = lines
+        .last()
+        .filter(|line| line.trim() == "*/" && lines.len() > 1);
+    let start = usize::from(opening.is_some());
+    let end = (lines.len() - usize::from(closing.is_some())).max(start);
+    let body = lines[start..end].join("\n");
+
+    let dedent_options = DedentOptions::new();
+    let level = dedent::detect_indent(&body, dedent_options);
+    let dedented = dedent::dedent_with(&body, dedent_options)?;
+    let indent = if body.starts_with('\t') {
+        // any columns that don't make up a whole tab are kept as spaces
+        let tab_width = dedent_options.tab_width();
+        IndentOptions::tabs(level / tab_width).make_indent()
+            + &IndentOptions::spaces(level % tab_width).make_indent()
+    } else {
+        IndentOptions::spaces(level).make_indent()
+    };
+
+    let body = dedented.lines().collect::<Vec<_>>();
+    let prefix = detect_prefix(&body);
+    let stripped = body
+        .iter()
+        .map(|line| {
+            let rest = line.trim_start().strip_prefix(prefix).unwrap_or(line);
+            rest.strip_prefix(' ').unwrap_or(rest)
+        })
+        .collect::<Vec<_>>();
+
+    let used = ansi::visible_width(&indent)
+        + ansi::visible_width(prefix)
+        + usize::from(!prefix.is_empty());
+    let nested = options
+        .clone()
+        .with_limit(options.limit().saturating_sub(used).max(1));
+
+    let mut output = Vec::with_capacity(lines.len());
+    output.extend(opening.map(ToString::to_string));
+    for line in markdown::reflow(&stripped, &nested)? {
+        output.push(if line.is_empty() && prefix.is_empty() {
+            line
+        } else if line.is_empty() || prefix.is_empty() {
+            format!("{indent}{prefix}{line}")
+        } else {
+            format!("{indent}{prefix} {line}")
+        });
+    }
+    output.extend(closing.map(ToString::to_string));
+
+    let line_ending = options.get_line_ending(input);
+    let mut output = output.join(line_ending);
+    if input.ends_with('\n') {
+        output.push_str(line_ending);
+    }
+    Ok(output)
+}
+
+/// Finds the first of the [`COMMENT_PREFIXES`] that every non-blank line starts with, or an empty
+/// string if there is none.
+fn detect_prefix(lines: &[&str]) -> &'static str {
+    COMMENT_PREFIXES
+        .iter()
+        .find(|prefix| {
+            lines
+                .iter()
+                .filter(|line| !line.trim().is_empty())
+                .all(|line| line.trim_start().starts_with(*prefix))
+                && lines.iter().any(|line| !line.trim().is_empty())
+        })
+        .copied()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::wordwrap_comment_str;
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn prefixes() {
+        let options = WrapOptions::new(20);
+        let cases = [
+            (
+                "/// Some doc comment that is too long.\n///\n/// More.",
+                "/// Some doc comment\n/// that is too\n/// long.\n///\n/// More.",
+            ),
+            (
+                "// short\n// lines that get joined",
+                "// short lines that\n// get joined",
+            ),
+            (
+                "# a shell comment that needs wrapping",
+                "# a shell comment\n# that needs\n# wrapping",
+            ),
+            (
+                "-- select everything from the table",
+                "-- select everything\n-- from the table",
+            ),
+            (
+                "> quoted email text that is long",
+                "> quoted email text\n> that is long",
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(wordwrap_comment_str(input, &options).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn indented_and_block_comments() {
+        let options = WrapOptions::new(24);
+        assert_eq!(
+            wordwrap_comment_str("    /// Doc comment for an indented item.", &options).unwrap(),
+            "    /// Doc comment for\n    /// an indented\n    /// item."
+        );
+        assert_eq!(
+            wordwrap_comment_str("\t// tabs are kept as tabs", &options).unwrap(),
+            "\t// tabs are kept\n\t// as tabs"
+        );
+        assert_eq!(
+            wordwrap_comment_str("\t  // tabs and spaces are kept\n", &options).unwrap(),
+            "\t  // tabs and\n\t  // spaces are\n\t  // kept\n"
+        );
+        assert_eq!(
+            wordwrap_comment_str("// a trailing newline is kept\r\n", &options).unwrap(),
+            "// a trailing newline is\r\n// kept\r\n"
+        );
+        assert_eq!(
+            wordwrap_comment_str("/**\n * A block comment that\n * wraps.\n */", &options).unwrap(),
+            "/**\n * A block comment that\n * wraps.\n */"
+        );
+        assert_eq!(
+            wordwrap_comment_str(
+                "  /**\n   * A block comment that is indented.\n   */",
+                &options
+            )
+            .unwrap(),
+            "  /**\n   * A block comment\n   * that is indented.\n   */"
+        );
+    }
+
+    #[test]
+    fn links() {
+        // like markdown, lines are only broken at whitespace and never hyphenated
+        let input = "/// See [the PR](https://github.com/org/some-repo/pull/12) ok";
+        let expected = "/// See [the\n/// PR](https://github.com/org/some-repo/pull/12)\n/// ok";
+        assert_eq!(
+            wordwrap_comment_str(input, &WrapOptions::new(30)).unwrap(),
+            expected
+        );
+        let options =
+            WrapOptions::new(30).with_hyphenation(crate::hyphenate::Hyphenator::english());
+        assert_eq!(wordwrap_comment_str(input, &options).unwrap(), expected);
+    }
+
+    #[test]
+    fn code_in_doc_comments() {
+        let input = "/// Example:\n/// ```\n/// let x = some_function_with_a_long_name();\n/// ```";
+        assert_eq!(
+            wordwrap_comment_str(input, &WrapOptions::new(20)).unwrap(),
+            input
+        );
+    }
+}
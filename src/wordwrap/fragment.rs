@@ -0,0 +1,272 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::{
+    ansi,
+    hyphenate::{self, Hyphenator, SOFT_HYPHEN},
+    iter::{BreakOpportunity, LineBreakClass, LineBreaks, Token, Tokens},
+};
+
+use super::{BreakStrategy, WrapOptions};
+
+/// A single unbreakable piece of a paragraph, along with the whitespace preceding it.
+#[derive(Debug, Default)]
+pub(super) struct Fragment {
+    pub glue: String,
+    pub glue_width: usize,
+    pub text: String,
+    pub width: usize,
+    /// Whether breaking after this fragment breaks a hyphenated word.
+    pub hyphen: bool,
+    /// Whether this fragment is part of a word that was split by hyphenation, meaning a hyphen
+    /// must be inserted if the line is broken after it.
+    pub discretionary: bool,
+    /// Whether the line **must** be broken after this fragment.
+    pub mandatory: bool,
+}
+
+impl Fragment {
+    fn finish(&mut self) {
+        self.glue_width = ansi::visible_width(&self.glue);
+        self.width = ansi::visible_width(&self.text);
+    }
+}
+
+/// Splits a paragraph into fragments using the [`BreakStrategy`] from `options`. Ansi escape
+/// sequences are kept with the word they are attached to, and any leading whitespace is kept with
+/// the first fragment.
+pub(super) fn split(paragraph: &str, options: &WrapOptions) -> Vec<Fragment> {
+    let mut fragments = match options.break_strategy() {
+        BreakStrategy::Breakpoints => split_breakpoints(paragraph, options),
+        BreakStrategy::UnicodeLineBreak => split_unicode(paragraph),
+    };
+
+    // soft hyphens are always honored, even if hyphenation isn't enabled
+    let soft_hyphens;
+    let hyphenator = match options.hyphenator() {
+        None if paragraph.contains(SOFT_HYPHEN) => {
+            soft_hyphens = Hyphenator::new();
+            Some(&soft_hyphens)
+        }
+        hyphenator => hyphenator,
+    };
+    if let Some(hyphenator) = hyphenator {
+        fragments = fragments
+            .into_iter()
+            .flat_map(|fragment| hyphenate(fragment, hyphenator))
+            .collect();
+    }
+
+    if let Some(first) = fragments.first_mut() {
+        first.text.insert_str(0, &std::mem::take(&mut first.glue));
+        first.finish();
+    }
+
+    fragments
+}
+
+/// Lays out the given fragments greedily, filling each line with as many fragments as fit. Words
+/// that were split by hyphenation are broken at the last piece that still fits (along with the
+/// hyphen) if the whole word doesn't.
+///
+/// `active` holds the styles active before the fragments, and is updated to the styles active
+/// after them.
+pub(super) fn layout_greedy(
+    fragments: &[Fragment],
+    options: &WrapOptions,
+    line_ending: &str,
+    active: &mut String,
+) -> String {
+    let limit = options.limit();
+    let hyphen_width = ansi::visible_width(options.hyphen());
+    let mut output = String::new();
+    let mut line_len = 0usize;
+    let mut line_start = true;
+
+    for (k, fragment) in fragments.iter().enumerate() {
+        // the width of the rest of the word this fragment belongs to
+        let rest = fragments[k..]
+            .iter()
+            .position(|f| !f.discretionary)
+            .map_or(&fragments[k..], |end| &fragments[k..=k + end])
+            .iter()
+            .map(|f| f.width)
+            .sum::<usize>();
+
+        // tabs in the glue extend to the next tab stop, wherever it ends up
+        let glue_end = ansi::visible_width_from(line_len, &fragment.glue);
+        if line_start {
+            line_len = 0;
+        } else if glue_end + rest <= limit
+            || (fragment.discretionary && glue_end + fragment.width + hyphen_width <= limit)
+        {
+            output.push_str(&fragment.glue);
+            line_len = glue_end;
+        } else {
+            if fragments[k - 1].discretionary {
+                push_hyphen(&mut output, options.hyphen(), active);
+            }
+            output.push_str(line_ending);
+            line_len = 0;
+        }
+
+        output.push_str(&fragment.text);
+        track_style(active, &fragment.text);
+        line_len += fragment.width;
+        line_start = fragment.mandatory;
+        if fragment.mandatory {
+            output.push_str(line_ending);
+        }
+    }
+
+    output
+}
+
+/// Updates `active` with the SGR escape sequences in `text`, so it holds the styles (set since the
+/// last reset) that are active at its end.
+pub(super) fn track_style(active: &mut String, text: &str) {
+    for token in Tokens::new(text) {
+        if token.is_reset() {
+            active.clear();
+        } else if token.is_sgr() {
+            active.push_str(token.as_str());
+        }
+    }
+}
+
+/// Pushes the (possibly styled) `hyphen` to the end of `output`. A styled hyphen usually resets
+/// its own style, which would also reset the style of the word it splits, so the `active` styles
+/// are restored after it.
+pub(super) fn push_hyphen(output: &mut String, hyphen: &str, active: &str) {
+    output.push_str(hyphen);
+    if hyphen.contains(ansi::MARKER) {
+        output.push_str(active);
+    }
+}
+
+/// Splits a fragment into pieces at the places the [`Hyphenator`] allows it to be hyphenated at,
+/// removing any soft hyphens. A fragment that ends in a soft hyphen (which is where
+/// [`BreakStrategy::UnicodeLineBreak`] breaks them) is hyphenated at its end.
+fn hyphenate(fragment: Fragment, hyphenator: &Hyphenator) -> Vec<Fragment> {
+    let points = hyphenator.hyphenate(&fragment.text);
+    let soft_end = hyphenate::visible_chars(&fragment.text)
+        .last()
+        .is_some_and(|(_, ch)| *ch == SOFT_HYPHEN);
+    if points.is_empty() && !fragment.text.contains(SOFT_HYPHEN) {
+        return vec![fragment];
+    }
+
+    let mut starts = Vec::with_capacity(points.len() + 1);
+    starts.push(0);
+    starts.extend(points);
+    let ends = starts
+        .iter()
+        .skip(1)
+        .copied()
+        .chain(std::iter::once(fragment.text.len()));
+
+    let last = starts.len() - 1;
+    starts
+        .iter()
+        .zip(ends)
+        .enumerate()
+        .map(|(i, (&start, end))| {
+            let mut piece = Fragment {
+                glue: if i == 0 {
+                    fragment.glue.clone()
+                } else {
+                    String::new()
+                },
+                text: fragment.text[start..end].replace(SOFT_HYPHEN, ""),
+                hyphen: i != last || soft_end || fragment.hyphen,
+                discretionary: i != last || soft_end,
+                mandatory: i == last && fragment.mandatory,
+                ..Fragment::default()
+            };
+            piece.finish();
+            piece
+        })
+        .collect()
+}
+
+fn split_breakpoints(paragraph: &str, options: &WrapOptions) -> Vec<Fragment> {
+    let mut fragments = Vec::new();
+    let mut current = Fragment::default();
+
+    for token in Tokens::new(paragraph) {
+        let text = match token {
+            Token::Text(text) => text,
+            Token::Escape { raw, .. } => {
+                current.text.push_str(raw);
+                continue;
+            }
+        };
+
+        for ch in text.chars() {
+            if ch.is_whitespace() {
+                if current.text.is_empty() {
+                    current.glue.push(ch);
+                } else {
+                    current.finish();
+                    fragments.push(std::mem::take(&mut current));
+                    current.glue.push(ch);
+                }
+            } else if options.is_breakpoint(ch) {
+                current.text.push(ch);
+                current.hyphen = true;
+                current.finish();
+                fragments.push(std::mem::take(&mut current));
+            } else {
+                current.text.push(ch);
+            }
+        }
+    }
+
+    if !current.text.is_empty() {
+        current.finish();
+        fragments.push(current);
+    }
+
+    fragments
+}
+
+fn split_unicode(paragraph: &str) -> Vec<Fragment> {
+    let mut fragments = Vec::new();
+    let mut glue = String::new();
+    let mut start = 0;
+
+    for (end, opportunity) in LineBreaks::new(paragraph) {
+        let segment = &paragraph[start..end];
+        start = end;
+
+        // the whitespace at the end of a segment is only visible if no break happens there
+        let text = segment.trim_end_matches(char::is_whitespace);
+        if text.is_empty() {
+            glue.push_str(segment);
+            continue;
+        }
+
+        let mandatory = opportunity == BreakOpportunity::Mandatory && end < paragraph.len();
+        let hyphen = text.chars().last().is_some_and(|ch| {
+            LineBreakClass::of(ch) == LineBreakClass::HY || matches!(ch, '\u{2010}' | '\u{2013}')
+        });
+        let mut fragment = Fragment {
+            glue: std::mem::take(&mut glue),
+            text: text.to_string(),
+            hyphen,
+            mandatory,
+            ..Fragment::default()
+        };
+        fragment.finish();
+        fragments.push(fragment);
+
+        if !mandatory {
+            glue.push_str(&segment[text.len()..]);
+        }
+    }
+
+    fragments
+}
@@ -0,0 +1,559 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Markdown-aware reflowing.
+//!
+//! The input is split into (CommonMark-ish) blocks line by line. Paragraphs are re-wrapped, code
+//! blocks (fenced and indented), headings, thematic breaks, tables and html blocks are copied
+//! verbatim, and the contents of list items and blockquotes are reflowed recursively before being
+//! re-prefixed with their marker (list items getting a hanging indent as wide as their marker).
+//! Paragraphs are only broken at whitespace, so urls and code spans stay intact.
+
+use crate::{indent::IndentOptions, Result};
+
+use super::{wordwrap_str, WrapOptions};
+
+/// The columns between tab stops when determining the block structure. The markdown spec expands
+/// tabs to multiples of 4 for this, regardless of how wide they are displayed (see
+/// [`ansi::TAB_WIDTH`](`crate::ansi::TAB_WIDTH`)).
+const TAB_STOP: usize = 4;
+
+/// Reflows the given markdown lines, returning the output lines. Lines are only broken at
+/// whitespace (see [`WrapOptions::whitespace_only`]).
+pub(super) fn reflow(lines: &[&str], options: &WrapOptions) -> Result<Vec<String>> {
+    blocks(lines, &options.whitespace_only())
+}
+
+/// Reflows the given markdown lines block by block, the contents of containers recursively.
+fn blocks(lines: &[&str], options: &WrapOptions) -> Result<Vec<String>> {
+    let mut output = Vec::with_capacity(lines.len());
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim().is_empty() {
+            output.push(String::new());
+            i += 1;
+            continue;
+        }
+
+        // the end of the block starting on this line, if it should be copied verbatim
+        let end = if let Some(fence) = fence(line) {
+            // everything up to and including the closing fence
+            lines[i + 1..]
+                .iter()
+                .position(|l| is_closing_fence(l, fence))
+                .map_or(lines.len(), |p| i + p + 2)
+        } else if indent_width(line) >= 4 {
+            // an indented code block continues until the first non-blank line that isn't indented
+            lines[i + 1..]
+                .iter()
+                .position(|l| !l.trim().is_empty() && indent_width(l) < 4)
+                .map_or(lines.len(), |p| i + p + 1)
+        } else if is_heading(line) || is_thematic_break(line) {
+            i + 1
+        } else if is_table(lines, i) || is_html(line) {
+            // tables and html blocks continue until the next blank line
+            lines[i + 1..]
+                .iter()
+                .position(|l| l.trim().is_empty())
+                .map_or(lines.len(), |p| i + p + 1)
+        } else if blockquote(line).is_some() {
+            let end = lines[i + 1..]
+                .iter()
+                .position(|l| blockquote(l).is_none())
+                .map_or(lines.len(), |p| i + p + 1);
+            let inner = lines[i..end]
+                .iter()
+                .filter_map(|l| blockquote(l))
+                .collect::<Vec<_>>();
+            for l in blocks(&inner, &nested(options, 2))? {
+                output.push(if l.is_empty() {
+                    ">".to_string()
+                } else {
+                    format!("> {l}")
+                });
+            }
+            i = end;
+            continue;
+        } else if let Some((marker, width)) = list_marker(line) {
+            let end = list_item_end(lines, i, width);
+            let inner = std::iter::once(&line[marker..])
+                .chain(lines[i + 1..end].iter().map(|l| strip_indent(l, width)))
+                .collect::<Vec<_>>();
+            let hanging = IndentOptions::spaces(width);
+            for (n, l) in blocks(&inner, &nested(options, width))?.iter().enumerate() {
+                output.push(if n == 0 {
+                    format!("{}{l}", &line[..marker])
+                } else if l.is_empty() {
+                    String::new()
+                } else {
+                    hanging.indent_line_unchecked(l)
+                });
+            }
+            i = end;
+            continue;
+        } else {
+            let end = lines[i + 1..]
+                .iter()
+                .position(|l| l.trim().is_empty() || interrupts_paragraph(l))
+                .map_or(lines.len(), |p| i + p + 1);
+            if end < lines.len() && is_setext_underline(lines[end]) {
+                // the whole paragraph is a heading
+                end + 1
+            } else {
+                output.extend(paragraph(&lines[i..end], options)?);
+                i = end;
+                continue;
+            }
+        };
+
+        output.extend(lines[i..end].iter().map(ToString::to_string));
+        i = end;
+    }
+
+    Ok(output)
+}
+
+/// Re-wraps a paragraph, keeping any hard line breaks (two trailing spaces or a backslash). No line
+/// is left starting with something that would start a new block.
+fn paragraph(lines: &[&str], options: &WrapOptions) -> Result<Vec<String>> {
+    let mut output = Vec::new();
+    let mut text = String::new();
+
+    for (n, line) in lines.iter().enumerate() {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(line.trim());
+
+        let hard_break = line.ends_with("  ") || line.ends_with('\\');
+        if hard_break || n + 1 == lines.len() {
+            let wrapped = wordwrap_str(&text, options)?;
+            output.extend(keep_in_paragraph(
+                wrapped.lines().map(ToString::to_string).collect(),
+            ));
+            if hard_break && line.ends_with("  ") {
+                if let Some(last) = output.last_mut() {
+                    last.push_str("  ");
+                }
+            }
+            text.clear();
+        }
+    }
+
+    Ok(output)
+}
+
+/// Pulls the last word of the previous line onto every wrapped line that would otherwise start a
+/// new block (e.g. a line starting with `- ` becoming a list item), or joins the lines if the
+/// previous line has only one word.
+fn keep_in_paragraph(mut lines: Vec<String>) -> Vec<String> {
+    let mut i = 1;
+    while i < lines.len() {
+        if !interrupts_paragraph(&lines[i]) && !is_setext_underline(&lines[i]) {
+            i += 1;
+            continue;
+        }
+
+        let line = lines.remove(i);
+        let previous = lines[i - 1].trim_end();
+        if let Some((start, word)) = previous.rsplit_once(' ') {
+            let start = start.trim_end().to_string();
+            lines.insert(i, format!("{word} {line}"));
+            lines[i - 1] = start;
+        } else {
+            lines[i - 1] = format!("{previous} {line}");
+        }
+    }
+
+    lines
+}
+
+/// Creates the options used for the contents of a container whose prefix is `width` columns wide.
+fn nested(options: &WrapOptions, width: usize) -> WrapOptions {
+    options
+        .clone()
+        .with_limit(options.limit().saturating_sub(width).max(1))
+}
+
+/// Finds the end of the list item starting on line `start` whose content starts at `marker`.
+fn list_item_end(lines: &[&str], start: usize, marker: usize) -> usize {
+    let mut end = start + 1;
+    while end < lines.len() {
+        let line = lines[end];
+        if line.trim().is_empty() {
+            // blank lines only belong to the item if it continues after them
+            match lines[end..].iter().position(|l| !l.trim().is_empty()) {
+                Some(p) if indent_width(lines[end + p]) >= marker => end += p,
+                _ => break,
+            }
+        } else if indent_width(line) >= marker
+            || (!lines[end - 1].trim().is_empty()
+                && !interrupts_paragraph(line)
+                && list_marker(line).is_none())
+        {
+            // indented continuation or lazy continuation of a paragraph (any list item ends it,
+            // since it isn't interrupting the paragraph inside this item)
+            end += 1;
+        } else {
+            break;
+        }
+    }
+
+    end
+}
+
+/// The column reached after `ch`, starting at `column`, with tabs stopping at multiples of
+/// [`TAB_STOP`].
+fn advance(column: usize, ch: char) -> usize {
+    if ch == '\t' {
+        column + TAB_STOP - column % TAB_STOP
+    } else {
+        column + 1
+    }
+}
+
+/// The width of the leading whitespace of a line, in columns.
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|ch| ch.is_whitespace())
+        .fold(0, advance)
+}
+
+/// Removes up to `width` columns of leading whitespace from a line.
+fn strip_indent(line: &str, width: usize) -> &str {
+    let mut removed = 0;
+    for (i, ch) in line.char_indices() {
+        if removed >= width || !(ch == ' ' || ch == '\t') {
+            return &line[i..];
+        }
+        removed = advance(removed, ch);
+    }
+
+    ""
+}
+
+/// Whether a line can start a new block in the middle of a paragraph. Only ordered lists starting
+/// at 1 can.
+fn interrupts_paragraph(line: &str) -> bool {
+    let starts_at_one = || {
+        let digits = line
+            .trim_start()
+            .split(|ch: char| !ch.is_ascii_digit())
+            .next()
+            .unwrap_or_default();
+        digits.is_empty() || digits.trim_start_matches('0') == "1"
+    };
+
+    fence(line).is_some()
+        || is_heading(line)
+        || is_thematic_break(line)
+        || blockquote(line).is_some()
+        || (list_marker(line).is_some() && starts_at_one())
+}
+
+/// Gets the fence character and length if the line opens a fenced code block.
+fn fence(line: &str) -> Option<(char, usize)> {
+    if indent_width(line) >= 4 {
+        return None;
+    }
+
+    let trimmed = line.trim_start();
+    let ch = trimmed
+        .chars()
+        .next()
+        .filter(|ch| *ch == '`' || *ch == '~')?;
+    let len = trimmed.chars().take_while(|c| *c == ch).count();
+    (len >= 3).then_some((ch, len))
+}
+
+fn is_closing_fence(line: &str, (ch, len): (char, usize)) -> bool {
+    let trimmed = line.trim();
+    indent_width(line) < 4
+        && trimmed.chars().take_while(|c| *c == ch).count() >= len
+        && trimmed.chars().all(|c| c == ch)
+}
+
+fn is_heading(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|ch| *ch == '#').count();
+    indent_width(line) < 4
+        && (1..=6).contains(&level)
+        && trimmed[level..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+}
+
+fn is_thematic_break(line: &str) -> bool {
+    let trimmed = line.trim();
+    indent_width(line) < 4
+        && ['-', '*', '_'].into_iter().any(|ch| {
+            trimmed.chars().filter(|c| *c == ch).count() >= 3
+                && trimmed.chars().all(|c| c == ch || c == ' ' || c == '\t')
+        })
+}
+
+fn is_setext_underline(line: &str) -> bool {
+    let trimmed = line.trim();
+    indent_width(line) < 4
+        && !trimmed.is_empty()
+        && (trimmed.chars().all(|c| c == '=') || trimmed.chars().all(|c| c == '-'))
+}
+
+/// Whether line `i` starts a table, i.e. it contains a `|` and is followed by a delimiter row
+/// (or it starts with a `|` itself).
+fn is_table(lines: &[&str], i: usize) -> bool {
+    let is_delimiter_row = |line: &str| {
+        let trimmed = line.trim();
+        trimmed.contains('-')
+            && trimmed.contains('|')
+            && trimmed
+                .chars()
+                .all(|c| matches!(c, '|' | ':' | '-' | ' ' | '\t'))
+    };
+
+    lines[i].trim_start().starts_with('|')
+        || (lines[i].contains('|') && lines.get(i + 1).is_some_and(|l| is_delimiter_row(l)))
+}
+
+/// Gets the contents of a blockquote line, without the `>` marker.
+fn blockquote(line: &str) -> Option<&str> {
+    if indent_width(line) >= 4 {
+        return None;
+    }
+
+    let rest = line.trim_start().strip_prefix('>')?;
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+/// Gets the length (in bytes, which are all ascii) and the width (in columns, with tabs stopping at
+/// multiples of [`TAB_STOP`]) of the list marker, including any leading indentation and the whitespace
+/// following it, if the line starts a list item.
+fn list_marker(line: &str) -> Option<(usize, usize)> {
+    if indent_width(line) >= 4 || is_thematic_break(line) {
+        return None;
+    }
+
+    let leading = line.len() - line.trim_start().len();
+    let rest = &line[leading..];
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    let marker = match rest[digits..].chars().next()? {
+        '-' | '*' | '+' if digits == 0 => 1,
+        '.' | ')' if (1..=9).contains(&digits) => digits + 1,
+        _ => return None,
+    };
+
+    let after = &rest[marker..];
+    let spaces = after
+        .chars()
+        .take_while(|ch| *ch == ' ' || *ch == '\t')
+        .count();
+    if after.is_empty() || spaces == 0 {
+        return None;
+    }
+
+    let columns = |len: usize| line[..len].chars().fold(0, advance);
+    let mut len = leading + marker + spaces;
+    // more than 4 columns of whitespace after the marker means the item starts with an indented
+    // code block
+    if columns(len) - columns(leading + marker) > 4 {
+        len = leading + marker + 1;
+    }
+
+    Some((len, columns(len)))
+}
+
+/// Whether the line starts an html block, i.e. it starts with an html tag, comment, processing
+/// instruction or declaration (but not an autolink such as `<https://example.com>`).
+fn is_html(line: &str) -> bool {
+    if indent_width(line) >= 4 {
+        return false;
+    }
+    let Some(rest) = line.trim_start().strip_prefix('<') else {
+        return false;
+    };
+    if rest.starts_with(['!', '?']) {
+        return true;
+    }
+
+    let rest = rest.strip_prefix('/').unwrap_or(rest);
+    let name = rest
+        .chars()
+        .take_while(|ch| ch.is_ascii_alphanumeric() || *ch == '-')
+        .count();
+    rest.starts_with(|ch: char| ch.is_ascii_alphabetic())
+        && rest[name..]
+            .chars()
+            .next()
+            .is_none_or(|ch| ch.is_whitespace() || ch == '>' || ch == '/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::wordwrap_markdown_str;
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn paragraphs() {
+        let input =
+            "Some text that is\ngoing to be reflowed.\n\nAnother    paragraph  \nwith a break.";
+        assert_eq!(
+            wordwrap_markdown_str(input, &WrapOptions::new(14)).unwrap(),
+            "Some text that\nis going to be\nreflowed.\n\nAnother\nparagraph  \nwith a break."
+        );
+    }
+
+    #[test]
+    fn verbatim_blocks() {
+        let input = "# A heading that is far too long\n\
+                     ```rust\nlet x = \"a very long line of code\";\n\n```\n\
+                     \n    indented code that is long\n\n\
+                     | a | b |\n|---|---|\n| long cell | long cell |\n\n\
+                     Setext heading that is long\n---\n\
+                     ***";
+        assert_eq!(
+            wordwrap_markdown_str(input, &WrapOptions::new(10)).unwrap(),
+            input
+        );
+    }
+
+    #[test]
+    fn lists() {
+        let input = "- one two three four\n- five six\n  seven eight\n\n  nine ten eleven\n\
+                     10. twelve thirteen\nfourteen";
+        assert_eq!(
+            wordwrap_markdown_str(input, &WrapOptions::new(12)).unwrap(),
+            "- one two\n  three four\n- five six\n  seven\n  eight\n\n  nine ten\n  eleven\n\
+             10. twelve\n    thirteen\n    fourteen"
+        );
+
+        // nested lists and code blocks inside list items
+        let input = "* aaa bbb\n  - ccc ddd eee\n\n  ```\n  code that is long\n  ```";
+        assert_eq!(
+            wordwrap_markdown_str(input, &WrapOptions::new(10)).unwrap(),
+            "* aaa bbb\n  - ccc\n    ddd\n    eee\n\n  ```\n  code that is long\n  ```"
+        );
+    }
+
+    #[test]
+    fn markers_and_html() {
+        // tabs may follow a list marker
+        assert_eq!(
+            wordwrap_markdown_str("-\tfoo bar baz", &WrapOptions::new(10)).unwrap(),
+            "-\tfoo\n    bar\n    baz"
+        );
+
+        // autolinks start paragraphs, unlike html tags
+        let input = "<https://example.com> is a\nlink\n\n<div>\nhtml that is kept\n</div>";
+        assert_eq!(
+            wordwrap_markdown_str(input, &WrapOptions::new(40)).unwrap(),
+            "<https://example.com> is a link\n\n<div>\nhtml that is kept\n</div>"
+        );
+    }
+
+    #[test]
+    fn links_and_code_spans() {
+        let options = WrapOptions::new(40);
+        assert_eq!(
+            wordwrap_markdown_str(
+                "See [the PR](https://github.com/org/some-repo/pull/12) for details here ok",
+                &options
+            )
+            .unwrap(),
+            "See [the\nPR](https://github.com/org/some-repo/pull/12)\nfor details here ok"
+        );
+        assert_eq!(
+            wordwrap_markdown_str(
+                "Run `cargo build --release-mode-foo` now",
+                &WrapOptions::new(24)
+            )
+            .unwrap(),
+            "Run `cargo build\n--release-mode-foo` now"
+        );
+        assert_eq!(
+            wordwrap_markdown_str(
+                "Docs at <https://example.com/a-b/c-d>",
+                &WrapOptions::new(20)
+            )
+            .unwrap(),
+            "Docs at\n<https://example.com/a-b/c-d>"
+        );
+    }
+
+    #[test]
+    fn trailing_newline() {
+        let options = WrapOptions::new(10);
+        assert_eq!(
+            wordwrap_markdown_str("some text that wraps\n", &options).unwrap(),
+            "some text\nthat wraps\n"
+        );
+        assert_eq!(
+            wordwrap_markdown_str("some text\r\n\r\n", &options).unwrap(),
+            "some text\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn no_new_blocks() {
+        // a wrapped line never starts something that would end the paragraph
+        let options = WrapOptions::new(14);
+        for (input, expected) in [
+            ("aaaa bbbb cccc - dddd", "aaaa bbbb\ncccc - dddd"),
+            ("aaaa bbbb cccc + dddd", "aaaa bbbb\ncccc + dddd"),
+            ("aaaa bbbb cccc # dddd", "aaaa bbbb\ncccc # dddd"),
+            ("aaaa bbbb cccc > dddd", "aaaa bbbb\ncccc > dddd"),
+            ("aaaa bbbb cccc 1. dddd", "aaaa bbbb\ncccc 1. dddd"),
+            ("aaaa bbbb cccc ***", "aaaa bbbb\ncccc ***"),
+            ("aaaa bbbb cccc ```", "aaaa bbbb\ncccc ```"),
+            ("aaaa bbbb cccc ===", "aaaa bbbb\ncccc ==="),
+            ("aaaaaaaaaaaaaa - - dddd", "aaaaaaaaaaaaaa - - dddd"),
+        ] {
+            let output = wordwrap_markdown_str(input, &options).unwrap();
+            assert_eq!(output, expected, "{input:?}");
+            // so reflowing again doesn't change anything
+            assert_eq!(wordwrap_markdown_str(&output, &options).unwrap(), output);
+        }
+
+        // only ordered lists starting at 1 interrupt a paragraph
+        assert_eq!(
+            wordwrap_markdown_str("It happened in\n2019. It was fun", &WrapOptions::new(40))
+                .unwrap(),
+            "It happened in 2019. It was fun"
+        );
+        assert_eq!(
+            wordwrap_markdown_str("It happened in 2019. It was fun", &options).unwrap(),
+            "It happened in\n2019. It was\nfun"
+        );
+        assert_eq!(
+            wordwrap_markdown_str("Steps:\n1. one\n2. two", &WrapOptions::new(40)).unwrap(),
+            "Steps:\n1. one\n2. two"
+        );
+    }
+
+    #[test]
+    fn tab_stops() {
+        // tabs in the block structure stop at multiples of 4
+        assert_eq!(indent_width("  \tx"), 4);
+        assert_eq!(indent_width("\t x"), 5);
+        assert_eq!(strip_indent("  \tx", 4), "x");
+        assert_eq!(
+            wordwrap_markdown_str("  \tcode that is long", &WrapOptions::new(10)).unwrap(),
+            "  \tcode that is long"
+        );
+    }
+
+    #[test]
+    fn blockquotes() {
+        let input = "> quoted text that\n> is long\n>\n> - item one two";
+        assert_eq!(
+            wordwrap_markdown_str(input, &WrapOptions::new(12)).unwrap(),
+            "> quoted\n> text that\n> is long\n>\n> - item one\n>   two"
+        );
+    }
+}
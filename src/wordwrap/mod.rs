@@ -0,0 +1,459 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+mod comment;
+mod fragment;
+mod markdown;
+mod optimal;
+mod options;
+mod writer;
+pub use comment::COMMENT_PREFIXES;
+pub use options::{
+    BreakStrategy, WrapOptions, DEFAULT_BREAKPOINTS, DEFAULT_HYPHEN, DEFAULT_HYPHEN_PENALTY,
+    DEFAULT_NEWLINE, DEFAULT_OVERFLOW_PENALTY,
+};
+pub use writer::Writer;
+
+use crate::{indent::LineEnding, Result};
+
+/// Greedily word-wraps the given `input` using the given [`options`](`WrapOptions`). Each line is
+/// filled with as many words as will fit before a new line is started.
+///
+/// ## Errors
+/// - `crate::Error::Utf8` if the internal word buffer somehow becomes invalid utf8
+///
+/// # Examples
+/// ```
+/// # use reflors::wordwrap::{wordwrap_str, WrapOptions};
+/// let output = wordwrap_str("aaa bb cc ddddd", &WrapOptions::new(6)).unwrap();
+/// assert_eq!(output, "aaa bb\ncc\nddddd");
+/// ```
+pub fn wordwrap_str(input: &str, options: &WrapOptions) -> Result<String> {
+    // the whole input is available, so the line endings can be detected up front
+    let line_ending = LineEnding::detect(input);
+    let options = options
+        .clone()
+        .with_line_endings(options.line_endings().or(Some(line_ending)));
+    let mut writer = Writer::with_options(options);
+    writer.write_str(input)?;
+    writer.close()?;
+    Ok(writer.into_string())
+}
+
+/// Word-wraps the given `input` using the given [`options`](`WrapOptions`), choosing the line
+/// breaks of each paragraph so that the total raggedness is minimized (Knuth-Plass style) rather
+/// than filling each line greedily. Accepts the same options as [`wordwrap_str`].
+///
+/// ## Errors
+/// - Currently never fails, the `Result` is kept for parity with [`wordwrap_str`]
+///
+/// # Examples
+/// ```
+/// # use reflors::wordwrap::{wordwrap_optimal_str, WrapOptions};
+/// let output = wordwrap_optimal_str("aaa bb cc ddddd", &WrapOptions::new(6)).unwrap();
+/// assert_eq!(output, "aaa\nbb cc\nddddd");
+/// ```
+pub fn wordwrap_optimal_str(input: &str, options: &WrapOptions) -> Result<String> {
+    Ok(optimal::wrap(input, options))
+}
+
+/// Reflows the given markdown `input` using the given [`options`](`WrapOptions`), preserving its
+/// structure. Paragraphs are re-wrapped greedily (see [`wordwrap_str`]), while fenced and indented
+/// code blocks, headings, thematic breaks, tables and html blocks are left alone. The contents of
+/// list items and blockquotes are reflowed to fit within their markers, with list items getting
+/// a hanging indent that matches the width of their marker.
+///
+/// Lines are only broken at whitespace, ignoring the [breakpoints](`WrapOptions::breakpoints`),
+/// [break strategy](`WrapOptions::break_strategy`) and hyphenation of the `options`, so link
+/// destinations, autolinks and code spans are never split.
+///
+/// ## Errors
+/// - `crate::Error::Utf8` if the internal word buffer somehow becomes invalid utf8
+///
+/// # Examples
+/// ```
+/// # use reflors::wordwrap::{wordwrap_markdown_str, WrapOptions};
+/// let input = "# Changes\n\n- Fixed the thing that\n  was broken\n\n```\nlet unchanged = true;\n```";
+/// let output = wordwrap_markdown_str(input, &WrapOptions::new(16)).unwrap();
+/// assert_eq!(
+///     output,
+///     "# Changes\n\n- Fixed the\n  thing that was\n  broken\n\n```\nlet unchanged = true;\n```"
+/// );
+/// ```
+pub fn wordwrap_markdown_str(input: &str, options: &WrapOptions) -> Result<String> {
+    if options.limit() == 0 {
+        return Ok(input.to_string());
+    }
+
+    let line_ending = options.get_line_ending(input);
+    let lines = input.lines().collect::<Vec<_>>();
+    let mut output = markdown::reflow(&lines, options)?.join(line_ending);
+    if input.ends_with('\n') {
+        output.push_str(line_ending);
+    }
+    Ok(output)
+}
+
+/// Reflows the given source-code comment using the given [`options`](`WrapOptions`), similar to
+/// vim's `gq`. The indentation and the comment prefix shared by every line (one of the
+/// [`COMMENT_PREFIXES`], e.g. `//`, `///`, `#`, `--`, ` * ` or `> `) are stripped, the remaining
+/// text is reflowed as markdown (see [`wordwrap_markdown_str`]) to a width that leaves room for
+/// them, and then they are re-applied to every line.
+///
+/// ## Errors
+/// - `crate::Error::Utf8` if the internal word buffer somehow becomes invalid utf8
+///
+/// # Examples
+/// ```
+/// # use reflors::wordwrap::{wordwrap_comment_str, WrapOptions};
+/// let input = "    // A comment that\n    // has been edited a few times.";
+/// let output = wordwrap_comment_str(input, &WrapOptions::new(30)).unwrap();
+/// assert_eq!(output, "    // A comment that has been\n    // edited a few times.");
+/// ```
+pub fn wordwrap_comment_str(input: &str, options: &WrapOptions) -> Result<String> {
+    if options.limit() == 0 {
+        return Ok(input.to_string());
+    }
+
+    comment::reflow(input, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hyphenate::Hyphenator;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn greedy_basic() {
+        let options = WrapOptions::new(10);
+        assert_eq!(
+            wordwrap_str("The quick brown fox jumps over the lazy dog", &options).unwrap(),
+            "The quick\nbrown fox\njumps over\nthe lazy\ndog"
+        );
+        assert_eq!(
+            wordwrap_str("foo-bar-baz", &WrapOptions::new(5)).unwrap(),
+            "foo-\nbar-\nbaz"
+        );
+        assert_eq!(
+            wordwrap_str("no limit", &WrapOptions::new(0)).unwrap(),
+            "no limit"
+        );
+
+        // a tab extends to the next tab stop, wherever it starts
+        let options = WrapOptions::new(10);
+        assert_eq!(wordwrap_str("ab\tcd", &options).unwrap(), "ab\tcd");
+        assert_eq!(wordwrap_str("ab\tcde", &options).unwrap(), "ab\ncde");
+        assert_eq!(wordwrap_optimal_str("ab\tcd", &options).unwrap(), "ab\tcd");
+        assert_eq!(
+            wordwrap_optimal_str("a\tb c\td ee", &options).unwrap(),
+            "a\tb\nc\td\nee"
+        );
+        assert_eq!(
+            wordwrap_optimal_str("ab cd\te", &options).unwrap(),
+            "ab cd\te"
+        );
+    }
+
+    #[test]
+    fn greedy_ansi() {
+        let input = "\u{1b}[38;2;249;38;114mfoo bar\u{1b}[0m baz";
+        assert_eq!(
+            wordwrap_str(input, &WrapOptions::new(7)).unwrap(),
+            "\u{1b}[38;2;249;38;114mfoo bar\u{1b}[0m\nbaz"
+        );
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(wordwrap_str(link, &WrapOptions::new(4)).unwrap(), link);
+        assert_eq!(
+            wordwrap_str(&format!("see {link}"), &WrapOptions::new(4)).unwrap(),
+            format!("see\n{link}")
+        );
+    }
+
+    #[test]
+    fn greedy_newlines() {
+        let input = "foo bar\nbaz qux";
+        assert_eq!(
+            wordwrap_str(input, &WrapOptions::new(20)).unwrap(),
+            "foo bar\nbaz qux"
+        );
+        assert_eq!(
+            wordwrap_str(input, &WrapOptions::new(20).with_keep_newlines(false)).unwrap(),
+            "foo bar baz qux"
+        );
+
+        // only the start and end of the whole stream are trimmed, not every chunk
+        let mut writer = Writer::with_options(WrapOptions::new(20).with_keep_newlines(false));
+        for chunk in ["  foo ", "bar", "\nbaz", " ", "qux \n"] {
+            writer.write_str(chunk).unwrap();
+        }
+        writer.close().unwrap();
+        assert_eq!(writer.into_string(), "foo bar baz qux");
+    }
+
+    #[test]
+    fn optimal_less_ragged() {
+        let options = WrapOptions::new(6);
+        assert_eq!(
+            wordwrap_str("aaa bb cc ddddd", &options).unwrap(),
+            "aaa bb\ncc\nddddd"
+        );
+        assert_eq!(
+            wordwrap_optimal_str("aaa bb cc ddddd", &options).unwrap(),
+            "aaa\nbb cc\nddddd"
+        );
+    }
+
+    #[test]
+    fn optimal_paragraphs_and_ansi() {
+        let input = "\u{1b}[1maaa\u{1b}[0m bb cc ddddd\n\n  aaa bb";
+        assert_eq!(
+            wordwrap_optimal_str(input, &WrapOptions::new(6)).unwrap(),
+            "\u{1b}[1maaa\u{1b}[0m\nbb cc\nddddd\n\n  aaa\nbb"
+        );
+    }
+
+    #[test]
+    fn optimal_penalties() {
+        let input = "x aaaa-bb cc";
+        // breaking after the hyphen leaves less space unused...
+        assert_eq!(
+            wordwrap_optimal_str(input, &WrapOptions::new(7).with_hyphen_penalty(0)).unwrap(),
+            "x aaaa-\nbb cc"
+        );
+        // ...unless it is penalized heavily enough
+        assert_eq!(
+            wordwrap_optimal_str(input, &WrapOptions::new(7).with_hyphen_penalty(100)).unwrap(),
+            "x\naaaa-bb\ncc"
+        );
+
+        let input = "aa bb cc";
+        assert_eq!(
+            wordwrap_optimal_str(input, &WrapOptions::new(7)).unwrap(),
+            "aa bb\ncc"
+        );
+        // a cheap enough overflow is preferred over a ragged line
+        assert_eq!(
+            wordwrap_optimal_str(input, &WrapOptions::new(7).with_overflow_penalty(1)).unwrap(),
+            "aa bb cc"
+        );
+        // words wider than the limit overflow regardless
+        assert_eq!(
+            wordwrap_optimal_str("aaaaaaaa bb", &WrapOptions::new(6)).unwrap(),
+            "aaaaaaaa\nbb"
+        );
+    }
+
+    #[test]
+    fn unicode_line_break() {
+        let options = WrapOptions::new(12).with_break_strategy(BreakStrategy::UnicodeLineBreak);
+        let input = "see https://example.com/docs/page for more";
+        assert_eq!(
+            wordwrap_str(input, &options).unwrap(),
+            "see https://\nexample.com/\ndocs/page\nfor more"
+        );
+        assert_eq!(
+            wordwrap_optimal_str(input, &options).unwrap(),
+            "see https://\nexample.com/\ndocs/page\nfor more"
+        );
+
+        // non-breaking spaces and word joiners keep words together
+        let input = "foo\u{a0}bar baz\u{2060}qux";
+        assert_eq!(
+            wordwrap_str(
+                input,
+                &WrapOptions::new(8).with_break_strategy(BreakStrategy::UnicodeLineBreak)
+            )
+            .unwrap(),
+            "foo\u{a0}bar\nbaz\u{2060}qux"
+        );
+        assert_eq!(
+            wordwrap_str(
+                "foo\u{200b}bar",
+                &WrapOptions::new(4).with_break_strategy(BreakStrategy::UnicodeLineBreak)
+            )
+            .unwrap(),
+            "foo\u{200b}\nbar"
+        );
+
+        // lines are broken between ideographs, but not before closing punctuation
+        let input = "\u{1b}[1m東京です。\u{1b}[0m\nfoo";
+        assert_eq!(
+            wordwrap_optimal_str(
+                input,
+                &WrapOptions::new(9).with_break_strategy(BreakStrategy::UnicodeLineBreak)
+            )
+            .unwrap(),
+            "\u{1b}[1m東京で\nす。\u{1b}[0m\nfoo"
+        );
+    }
+
+    #[test]
+    fn piped() {
+        use std::io::Write;
+
+        let mut writer = Writer::new_piped(WrapOptions::new(6), Vec::new());
+        write!(writer, "aaa bb cc ").unwrap();
+        // complete words are written straight away
+        assert_eq!(writer.into_inner(), b"aaa bb\ncc");
+
+        let mut writer = Writer::new_piped(WrapOptions::new(6), Vec::new());
+        writer.write_all(b"aaa bb cc dddd").unwrap();
+        writer.close().unwrap();
+        assert_eq!(writer.into_inner(), b"aaa bb\ncc\ndddd");
+
+        // characters split across writes are held back until they are complete
+        let text = "東京 タワー 駅";
+        for size in 1..text.len() {
+            let mut writer = Writer::new(6);
+            for chunk in text.as_bytes().chunks(size) {
+                assert_eq!(writer.write_bytes(chunk).unwrap(), chunk.len());
+            }
+            writer.close().unwrap();
+            assert_eq!(writer.into_string(), "東京\nタワー\n駅");
+        }
+    }
+
+    #[test]
+    fn line_endings() {
+        let input = "aaa bb cc\r\nddddd\r\n";
+        let options = WrapOptions::new(6);
+        assert_eq!(
+            wordwrap_str(input, &options).unwrap(),
+            "aaa bb\r\ncc\r\nddddd\r\n"
+        );
+        assert_eq!(
+            wordwrap_optimal_str(input, &options).unwrap(),
+            "aaa bb\r\ncc\r\nddddd\r\n"
+        );
+        assert_eq!(
+            wordwrap_str(
+                input,
+                &options
+                    .clone()
+                    .with_break_strategy(BreakStrategy::UnicodeLineBreak)
+            )
+            .unwrap(),
+            "aaa bb\r\ncc\r\nddddd\r\n"
+        );
+
+        // line endings can be forced
+        let to_lf = options.with_line_endings(Some(LineEnding::posix()));
+        assert_eq!(wordwrap_str(input, &to_lf).unwrap(), "aaa bb\ncc\nddddd\n");
+        assert_eq!(
+            wordwrap_optimal_str(input, &to_lf).unwrap(),
+            "aaa bb\ncc\nddddd\n"
+        );
+        assert_eq!(
+            wordwrap_markdown_str("- aaa bb\r\n  cc", &to_lf.clone().with_limit(8)).unwrap(),
+            "- aaa bb\n  cc"
+        );
+        assert_eq!(
+            wordwrap_comment_str("# aaa bb\r\n# cc", &WrapOptions::new(8)).unwrap(),
+            "# aaa bb\r\n# cc"
+        );
+    }
+
+    #[test]
+    fn hyphenation() {
+        let options = WrapOptions::new(14).with_hyphenation(Hyphenator::english());
+        let input = "the quick hyphenation";
+        assert_eq!(
+            wordwrap_str(input, &options).unwrap(),
+            "the quick hy-\nphenation"
+        );
+        assert_eq!(
+            wordwrap_optimal_str(input, &options).unwrap(),
+            "the quick\nhyphenation"
+        );
+        assert_eq!(
+            wordwrap_optimal_str(input, &options.clone().with_hyphen_penalty(0)).unwrap(),
+            "the quick hy-\nphenation"
+        );
+
+        // the hyphen may be styled, and is only ever inserted at the end of a line
+        let styled = options.with_hyphen("\u{1b}[2m-\u{1b}[0m");
+        assert_eq!(
+            wordwrap_str(input, &styled).unwrap(),
+            "the quick hy\u{1b}[2m-\u{1b}[0m\nphenation"
+        );
+
+        // escape sequences inside the word are kept intact
+        assert_eq!(
+            wordwrap_str(
+                "\u{1b}[31mhyphenation\u{1b}[0m",
+                &WrapOptions::new(6).with_hyphenation(Hyphenator::english())
+            )
+            .unwrap(),
+            "\u{1b}[31mhy-\nphen-\nation\u{1b}[0m"
+        );
+
+        // a styled hyphen doesn't reset the style of the word it splits
+        let red = "\u{1b}[31mhyphenation\u{1b}[0m";
+        let styled = WrapOptions::new(6)
+            .with_hyphenation(Hyphenator::english())
+            .with_hyphen("\u{1b}[2m-\u{1b}[0m");
+        let expected = "\u{1b}[31mhy\u{1b}[2m-\u{1b}[0m\u{1b}[31m\nphen\u{1b}[2m-\u{1b}[0m\u{1b}[31m\nation\u{1b}[0m";
+        assert_eq!(wordwrap_str(red, &styled).unwrap(), expected);
+        assert_eq!(wordwrap_optimal_str(red, &styled).unwrap(), expected);
+        assert_eq!(
+            wordwrap_str(
+                "\u{1b}[31msoft\u{ad}ware\u{1b}[0m",
+                &WrapOptions::new(5).with_hyphen("\u{1b}[2m-\u{1b}[0m")
+            )
+            .unwrap(),
+            "\u{1b}[31msoft\u{1b}[2m-\u{1b}[0m\u{1b}[31m\nware\u{1b}[0m"
+        );
+    }
+
+    #[test]
+    fn soft_hyphens() {
+        let options = WrapOptions::new(8).with_hyphenation(Hyphenator::new());
+        assert_eq!(
+            wordwrap_str("a soft\u{ad}ware thing", &options).unwrap(),
+            "a soft-\nware\nthing"
+        );
+        assert_eq!(
+            wordwrap_optimal_str("a soft\u{ad}ware thing", &options).unwrap(),
+            "a\nsoftware\nthing"
+        );
+        assert_eq!(
+            wordwrap_str(
+                "soft\u{ad}ware",
+                &WrapOptions::new(20).with_hyphenation(Hyphenator::new())
+            )
+            .unwrap(),
+            "software"
+        );
+
+        // soft hyphens are honored even without hyphenation
+        let options = WrapOptions::new(8);
+        assert_eq!(
+            wordwrap_str("a soft\u{ad}ware thing", &options).unwrap(),
+            "a soft-\nware\nthing"
+        );
+        assert_eq!(
+            wordwrap_str("a soft\u{ad}ware\u{ad}", &options).unwrap(),
+            "a soft-\nware"
+        );
+        assert_eq!(
+            wordwrap_optimal_str("a soft\u{ad}ware thing", &options).unwrap(),
+            "a\nsoftware\nthing"
+        );
+        assert_eq!(
+            wordwrap_str(
+                "x soft\u{ad}ware",
+                &options.with_break_strategy(BreakStrategy::UnicodeLineBreak)
+            )
+            .unwrap(),
+            "x soft-\nware"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Optimal (Knuth-Plass style) paragraph line breaking.
+//!
+//! Instead of filling each line as much as possible before moving on, every possible set of line
+//! breaks in a paragraph is considered and the one with the lowest total cost is chosen. The cost
+//! of a line is the square of its unused width (the last line of a paragraph is free), plus the
+//! [hyphen penalty](`WrapOptions::hyphen_penalty`) if it ends at a breakpoint, plus the
+//! [overflow penalty](`WrapOptions::overflow_penalty`) for each column it extends past the limit.
+
+use crate::ansi;
+
+use super::{fragment, WrapOptions};
+
+/// Wraps every paragraph (line) of `input` optimally according to `options`.
+pub(super) fn wrap(input: &str, options: &WrapOptions) -> String {
+    if options.limit() == 0 {
+        return input.to_string();
+    }
+
+    let line_ending = options.get_line_ending(input);
+    let input = options.prepare(input);
+    // the styles active at the end of the output so far
+    let mut active = String::new();
+    input
+        .split(|ch| options.is_newline(ch))
+        .map(|paragraph| {
+            let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+            wrap_paragraph(paragraph, options, line_ending, &mut active)
+        })
+        .collect::<Vec<_>>()
+        .join(line_ending)
+}
+
+fn wrap_paragraph(
+    paragraph: &str,
+    options: &WrapOptions,
+    line_ending: &str,
+    active: &mut String,
+) -> String {
+    let fragments = fragment::split(paragraph, options);
+    let hyphen_width = ansi::visible_width(options.hyphen());
+    let n = fragments.len();
+    // tabs extend to the next tab stop, so their width depends on where the line starts
+    let tabs = fragments
+        .iter()
+        .any(|fragment| fragment.glue.contains('\t'));
+
+    // best[i] is the lowest cost of laying out the first `i` fragments, with the last line
+    // starting at fragment prev[i].
+    let mut best = vec![usize::MAX; n + 1];
+    let mut prev = vec![0usize; n + 1];
+    best[0] = 0;
+
+    for i in 1..=n {
+        let last = i == n || fragments[i - 1].mandatory;
+        // breaking a hyphenated word adds a hyphen to the end of the line
+        let hyphen = if last || !fragments[i - 1].discretionary {
+            0
+        } else {
+            hyphen_width
+        };
+        let mut width = hyphen;
+        // with tabs, ends[c] is the column the line ends at if it starts at column c, which only
+        // has to be known within one tab stop since tabs repeat every `TAB_WIDTH` columns
+        let mut ends = [0; ansi::TAB_WIDTH];
+        for j in (0..i).rev() {
+            // a line can't continue past a mandatory break
+            if j + 1 < i && fragments[j].mandatory {
+                break;
+            }
+
+            width += fragments[j].width;
+            if j + 1 < i {
+                width += fragments[j + 1].glue_width;
+            }
+            if tabs {
+                ends = std::array::from_fn(|column| {
+                    let text_end = column + fragments[j].width;
+                    if j + 1 == i {
+                        return text_end;
+                    }
+                    let glue_end = ansi::visible_width_from(text_end, &fragments[j + 1].glue);
+                    let stop = glue_end - glue_end % ansi::TAB_WIDTH;
+                    stop + ends[glue_end % ansi::TAB_WIDTH]
+                });
+                width = hyphen + ends[0];
+            }
+
+            let cost = line_cost(width, last, fragments[i - 1].hyphen, options);
+            let total = best[j].saturating_add(cost);
+            if total < best[i] {
+                best[i] = total;
+                prev[i] = j;
+            }
+
+            // lines only get wider (and therefore more expensive) from here on
+            if width > options.limit() && cost >= best[i] {
+                break;
+            }
+        }
+    }
+
+    let mut breaks = Vec::new();
+    let mut i = n;
+    while i > 0 {
+        breaks.push((prev[i], i));
+        i = prev[i];
+    }
+
+    let mut output = String::new();
+    for (n, (start, end)) in breaks.into_iter().rev().enumerate() {
+        if n > 0 {
+            output.push_str(line_ending);
+        }
+
+        let line_start = output.len();
+        output.push_str(&fragments[start].text);
+        for fragment in &fragments[start + 1..end] {
+            output.push_str(&fragment.glue);
+            output.push_str(&fragment.text);
+        }
+        fragment::track_style(active, &output[line_start..]);
+        if end < fragments.len() && fragments[end - 1].discretionary {
+            fragment::push_hyphen(&mut output, options.hyphen(), active);
+        }
+    }
+
+    output
+}
+
+fn line_cost(width: usize, last: bool, hyphen: bool, options: &WrapOptions) -> usize {
+    let limit = options.limit();
+    let mut cost = if width > limit {
+        options.overflow_penalty().saturating_mul(width - limit)
+    } else if last {
+        0
+    } else {
+        (limit - width).saturating_mul(limit - width)
+    };
+
+    if hyphen && !last {
+        cost = cost.saturating_add(options.hyphen_penalty());
+    }
+
+    cost
+}
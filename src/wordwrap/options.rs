@@ -0,0 +1,277 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::sync::Arc;
+
+use crate::{hyphenate::Hyphenator, indent::LineEnding};
+
+/// The characters (other than whitespace) that a line is allowed to be broken after by default.
+pub const DEFAULT_BREAKPOINTS: &[char] = &['-'];
+
+/// The characters that are treated as a line break by default.
+pub const DEFAULT_NEWLINE: &[char] = &['\n'];
+
+/// The hyphen inserted at the end of a line when a word is hyphenated by default.
+pub const DEFAULT_HYPHEN: &str = "-";
+
+/// The default penalty applied by [`wordwrap_optimal_str`](`super::wordwrap_optimal_str`) when
+/// a line is broken after one of the [`WrapOptions::breakpoints`] or a hyphenated word is split.
+pub const DEFAULT_HYPHEN_PENALTY: usize = 50;
+
+/// The default penalty applied by [`wordwrap_optimal_str`](`super::wordwrap_optimal_str`) for
+/// **each** column a line extends past [`WrapOptions::limit`].
+pub const DEFAULT_OVERFLOW_PENALTY: usize = 1000;
+
+/// How the places that a line may be broken at are found.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BreakStrategy {
+    /// Lines may be broken at whitespace and after any of the [`WrapOptions::breakpoints`].
+    #[default]
+    Breakpoints,
+    /// Lines may be broken at the break opportunities found by the Unicode Line Breaking
+    /// Algorithm, see [`LineBreaks`](`crate::iter::LineBreaks`). The
+    /// [`WrapOptions::breakpoints`] are ignored.
+    UnicodeLineBreak,
+}
+
+/// Options for determining how text should be word-wrapped. The same options are accepted by both
+/// the greedy ([`wordwrap_str`](`super::wordwrap_str`)) and optimal
+/// ([`wordwrap_optimal_str`](`super::wordwrap_optimal_str`)) wrapping functions, the penalties are
+/// simply ignored by the greedy version.
+///
+/// Soft hyphens (U+00AD) in the input are always treated as places a word may be broken at, and
+/// are replaced with [`WrapOptions::hyphen`] if a line is broken there or removed otherwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrapOptions {
+    /// The maximum **visible** width of a line.
+    limit: usize,
+    /// Characters that a line may be broken after, in addition to whitespace.
+    breakpoints: Vec<char>,
+    /// Characters that are treated as line breaks.
+    newline: Vec<char>,
+    /// Whether line breaks in the input are kept, or replaced with spaces.
+    keep_newlines: bool,
+    /// The penalty for breaking a line after one of the `breakpoints`.
+    hyphen_penalty: usize,
+    /// The penalty for each column a line extends past the `limit`.
+    overflow_penalty: usize,
+    /// How the places that a line may be broken at are found.
+    break_strategy: BreakStrategy,
+    /// Used to split words that don't fit on a line, if any.
+    hyphenator: Option<Arc<Hyphenator>>,
+    /// The (possibly styled) hyphen inserted at the end of a line when a word is split.
+    hyphen: String,
+    /// The line endings the output should use. If none, they will be detected from the input.
+    line_endings: Option<LineEnding>,
+}
+
+impl Default for WrapOptions {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl WrapOptions {
+    /// Creates a new [`WrapOptions`] with the given `limit` and the default breakpoints, newlines
+    /// and penalties. A `limit` of zero disables wrapping entirely.
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            breakpoints: DEFAULT_BREAKPOINTS.to_vec(),
+            newline: DEFAULT_NEWLINE.to_vec(),
+            keep_newlines: true,
+            hyphen_penalty: DEFAULT_HYPHEN_PENALTY,
+            overflow_penalty: DEFAULT_OVERFLOW_PENALTY,
+            break_strategy: BreakStrategy::Breakpoints,
+            hyphenator: None,
+            hyphen: DEFAULT_HYPHEN.to_string(),
+            line_endings: None,
+        }
+    }
+
+    /// Gets a copy of these options that only breaks lines at whitespace (and soft hyphens), for
+    /// text whose words must be kept whole, like the urls and code spans of markdown.
+    pub(super) fn whitespace_only(&self) -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            break_strategy: BreakStrategy::Breakpoints,
+            hyphenator: None,
+            ..self.clone()
+        }
+    }
+
+    /// Sets the maximum **visible** width of a line.
+    #[must_use]
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Sets the characters (other than whitespace) that a line may be broken after.
+    #[must_use]
+    pub fn with_breakpoints(mut self, breakpoints: &[char]) -> Self {
+        self.breakpoints = breakpoints.to_vec();
+        self
+    }
+
+    /// Sets how the places that a line may be broken at are found.
+    #[must_use]
+    pub fn with_break_strategy(mut self, break_strategy: BreakStrategy) -> Self {
+        self.break_strategy = break_strategy;
+        self
+    }
+
+    /// Enables hyphenation of words using the given [`Hyphenator`]. Soft hyphens (U+00AD) in the
+    /// input are honored whether or not hyphenation is enabled.
+    #[must_use]
+    pub fn with_hyphenation(mut self, hyphenator: Hyphenator) -> Self {
+        self.hyphenator = Some(Arc::new(hyphenator));
+        self
+    }
+
+    /// Sets the hyphen inserted at the end of a line when a word is hyphenated, which may contain
+    /// ansi escape sequences to style it.
+    #[must_use]
+    pub fn with_hyphen(mut self, hyphen: &str) -> Self {
+        self.hyphen = hyphen.to_string();
+        self
+    }
+
+    /// Sets the characters that are treated as line breaks.
+    #[must_use]
+    pub fn with_newline(mut self, newline: &[char]) -> Self {
+        self.newline = newline.to_vec();
+        self
+    }
+
+    /// Sets whether line breaks in the input are kept. If `false` the input is trimmed and every
+    /// line break is replaced with a space before wrapping.
+    #[must_use]
+    pub fn with_keep_newlines(mut self, keep_newlines: bool) -> Self {
+        self.keep_newlines = keep_newlines;
+        self
+    }
+
+    /// Sets the line endings the output should use, or `None` to use the line endings detected
+    /// from the input.
+    #[must_use]
+    pub fn with_line_endings(mut self, line_endings: Option<LineEnding>) -> Self {
+        self.line_endings = line_endings;
+        self
+    }
+
+    /// Sets the penalty for breaking a line after one of the breakpoints.
+    #[must_use]
+    pub fn with_hyphen_penalty(mut self, penalty: usize) -> Self {
+        self.hyphen_penalty = penalty;
+        self
+    }
+
+    /// Sets the penalty for each column a line extends past the limit.
+    #[must_use]
+    pub fn with_overflow_penalty(mut self, penalty: usize) -> Self {
+        self.overflow_penalty = penalty;
+        self
+    }
+}
+
+impl WrapOptions {
+    /// Gets the maximum **visible** width of a line.
+    #[must_use]
+    pub const fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Gets the characters (other than whitespace) that a line may be broken after.
+    #[must_use]
+    pub fn breakpoints(&self) -> &[char] {
+        self.breakpoints.as_slice()
+    }
+
+    /// Gets how the places that a line may be broken at are found.
+    #[must_use]
+    pub const fn break_strategy(&self) -> BreakStrategy {
+        self.break_strategy
+    }
+
+    /// Gets the [`Hyphenator`] used to split words that don't fit on a line, if any.
+    #[must_use]
+    pub fn hyphenator(&self) -> Option<&Hyphenator> {
+        self.hyphenator.as_deref()
+    }
+
+    /// Gets the hyphen inserted at the end of a line when a word is hyphenated.
+    #[must_use]
+    pub fn hyphen(&self) -> &str {
+        self.hyphen.as_str()
+    }
+
+    /// Gets the characters that are treated as line breaks.
+    #[must_use]
+    pub fn newline(&self) -> &[char] {
+        self.newline.as_slice()
+    }
+
+    /// Gets whether line breaks in the input are kept.
+    #[must_use]
+    pub const fn keep_newlines(&self) -> bool {
+        self.keep_newlines
+    }
+
+    /// Gets the line endings the output should use, if they shouldn't be detected from the input.
+    #[must_use]
+    pub const fn line_endings(&self) -> Option<LineEnding> {
+        self.line_endings
+    }
+
+    /// Get the appropriate line ending string for these options and the given input.
+    #[must_use]
+    pub fn get_line_ending(&self, input: &str) -> &'static str {
+        match self.line_endings {
+            Some(line_ending) => line_ending.as_str(),
+            None => LineEnding::detect(input).as_str(),
+        }
+    }
+
+    /// Gets the penalty for breaking a line after one of the breakpoints.
+    #[must_use]
+    pub const fn hyphen_penalty(&self) -> usize {
+        self.hyphen_penalty
+    }
+
+    /// Gets the penalty for each column a line extends past the limit.
+    #[must_use]
+    pub const fn overflow_penalty(&self) -> usize {
+        self.overflow_penalty
+    }
+
+    /// Checks whether the given character is one of the breakpoints.
+    #[must_use]
+    pub fn is_breakpoint(&self, ch: char) -> bool {
+        self.breakpoints.contains(&ch)
+    }
+
+    /// Checks whether the given character is one of the newline characters.
+    #[must_use]
+    pub fn is_newline(&self, ch: char) -> bool {
+        self.newline.contains(&ch)
+    }
+
+    /// Prepares the given input according to [`WrapOptions::keep_newlines`], trimming it and
+    /// replacing line breaks with spaces if they should not be kept.
+    pub(crate) fn prepare(&self, input: &str) -> String {
+        if self.keep_newlines {
+            input.to_string()
+        } else {
+            input
+                .trim()
+                .chars()
+                .map(|ch| if self.is_newline(ch) { ' ' } else { ch })
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,369 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io::{Result as IoResult, Write as IoWrite};
+
+use crate::{
+    ansi::{self, buffer::Buffer as AnsiBuffer},
+    fmt::{FmtWriter, Utf8Decoder},
+    hyphenate::SOFT_HYPHEN,
+    iter::{EscapeBuffer, Token, Tokens},
+    pipeline::Reflow,
+    Error, Result,
+};
+
+use super::{fragment, BreakStrategy, WrapOptions};
+
+/// The line ending used before any `\r\n` line endings have been seen.
+const DEFAULT_LINE_ENDING: &str = "\n";
+
+/// A greedy, ansi-aware word-wrapping writer. Words are added to the current line for as long as
+/// they fit within [`WrapOptions::limit`], and a new line is started as soon as they don't.
+///
+/// When using [`BreakStrategy::UnicodeLineBreak`] or hyphenation each paragraph (line of input) is
+/// buffered until it is complete, since the places a line may be broken at depend on the characters
+/// following them.
+///
+/// Unless [`WrapOptions::line_endings`] is set, the line endings of the input are preserved, and
+/// the line breaks inserted while wrapping use `\r\n` once a `\r\n` line ending has been seen.
+///
+/// The wrapped output is written to any [`std::io::Write`], by default a [`String`] (through a
+/// [`FmtWriter`]), after each write.
+///
+/// Source: [muesli/reflow/wordwrap/wordwrap.go](https://github.com/muesli/reflow/blob/master/wordwrap/wordwrap.go)
+pub struct Writer<W = FmtWriter<String>> {
+    options: WrapOptions,
+    inner: W,
+    buf: String,
+    space: String,
+    word: AnsiBuffer,
+    paragraph: String,
+    line_len: usize,
+    escapes: EscapeBuffer,
+    soft_hyphen: bool,
+    crlf: bool,
+    /// The styles active at the end of the output so far.
+    style: String,
+    /// The whitespace at the end of the input so far, which is held back when line breaks aren't
+    /// kept since it is trimmed at the end of the stream. `None` until anything other than
+    /// whitespace has been written, since the start of the stream is trimmed too.
+    trailing: Option<String>,
+    char_buffer: [u8; 4],
+    decoder: Utf8Decoder,
+}
+
+impl Writer {
+    /// Creates a new [`Writer`] that wraps at `limit` using the default [`WrapOptions`].
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        Self::with_options(WrapOptions::new(limit))
+    }
+
+    /// Creates a new [`Writer`] using the given [`WrapOptions`].
+    #[must_use]
+    pub fn with_options(options: WrapOptions) -> Self {
+        Self::new_piped(options, FmtWriter::new(String::new()))
+    }
+
+    /// Gets the wrapped output written so far.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        self.inner.get_ref().as_str()
+    }
+
+    /// **Consumes** this [`Writer`] and returns the wrapped output.
+    #[must_use]
+    pub fn into_string(self) -> String {
+        self.inner.into_inner()
+    }
+}
+
+impl<W> Writer<W> {
+    /// Creates a new [`Writer`] using the given [`WrapOptions`] that writes to `writer`.
+    #[must_use]
+    pub fn new_piped(options: WrapOptions, writer: W) -> Self {
+        Self {
+            options,
+            inner: writer,
+            buf: String::new(),
+            space: String::new(),
+            word: AnsiBuffer::new(),
+            paragraph: String::new(),
+            line_len: 0,
+            escapes: EscapeBuffer::default(),
+            soft_hyphen: false,
+            crlf: false,
+            style: String::new(),
+            trailing: None,
+            char_buffer: [0; 4],
+            decoder: Utf8Decoder::default(),
+        }
+    }
+
+    /// Gets the [`WrapOptions`] used by this [`Writer`].
+    #[must_use]
+    pub fn options(&self) -> &WrapOptions {
+        &self.options
+    }
+
+    /// **Consumes** this [`Writer`] and returns the underlying writer, without
+    /// [closing](`Writer::close`) it.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: IoWrite> Writer<W> {
+    /// Write the given bytes to this [`Writer`]. A utf8 character may be split across writes, its
+    /// start is held back until the rest of it is written.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If `bytes` can't be valid utf8
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
+        let s = self.decoder.decode(bytes)?;
+        self.write_str(&s)?;
+        Ok(bytes.len())
+    }
+
+    /// Write the given string to this [`Writer`].
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If the internal word buffer somehow becomes invalid utf8
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn write_str(&mut self, s: &str) -> Result<usize> {
+        self.wrap_str(s)?;
+        self.flush_buf()?;
+        Ok(s.len())
+    }
+
+    /// Finishes wrapping by writing any word that is still pending to the output and flushing the
+    /// underlying writer.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If the internal word buffer somehow becomes invalid utf8
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn close(&mut self) -> Result<()> {
+        let held = self.escapes.take();
+        self.word.push_str(&held);
+        self.add_paragraph();
+        self.add_word()?;
+        self.flush_buf()?;
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    /// Writes the output wrapped so far to the underlying writer.
+    fn flush_buf(&mut self) -> Result<()> {
+        self.inner.write_all(self.buf.as_bytes())?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<W> Writer<W> {
+    fn wrap_str(&mut self, s: &str) -> Result<()> {
+        if self.options.limit() == 0 {
+            self.buf.push_str(s);
+            return Ok(());
+        }
+
+        let input = self.prepare(s);
+        if self.buffers_paragraphs() {
+            for ch in input.chars() {
+                if self.options.is_newline(ch) {
+                    if self.paragraph.ends_with('\r') {
+                        self.paragraph.pop();
+                        self.crlf = true;
+                    }
+                    self.add_paragraph();
+                    self.buf.push_str(self.line_ending());
+                } else {
+                    self.paragraph.push(ch);
+                }
+            }
+
+            return Ok(());
+        }
+
+        let input = self.escapes.complete(&input);
+        for token in Tokens::new(&input) {
+            let text = match token {
+                Token::Text(text) => text,
+                Token::Escape { raw, .. } => {
+                    self.word.push_str(raw);
+                    continue;
+                }
+            };
+
+            for ch in text.chars() {
+                if self.options.is_newline(ch) {
+                    if self.space.ends_with('\r') {
+                        self.space.pop();
+                        self.crlf = true;
+                    }
+
+                    // end of the current line, see if the pending space still fits on it
+                    if self.word.is_empty() {
+                        if ansi::visible_width_from(self.line_len, &self.space)
+                            > self.options.limit()
+                        {
+                            self.line_len = 0;
+                        } else {
+                            self.buf.push_str(&self.space);
+                        }
+                        self.space.clear();
+                    }
+
+                    self.add_word()?;
+                    self.soft_hyphen = false;
+                    self.add_newline();
+                } else if ch.is_whitespace() {
+                    self.add_word()?;
+                    self.soft_hyphen = false;
+                    self.space.push(ch);
+                } else if ch == SOFT_HYPHEN {
+                    // a possible break point, which only shows up if the line is broken here
+                    self.add_space();
+                    self.add_word()?;
+                    self.soft_hyphen = true;
+                } else if self.options.is_breakpoint(ch) {
+                    self.add_space();
+                    self.add_word()?;
+                    self.buf.push(ch);
+                    self.line_len += ansi::visible_width(ch.encode_utf8(&mut self.char_buffer));
+                } else {
+                    self.push_word_char(ch);
+
+                    // start a new line if the current word would exceed the limit
+                    let word_len = self.word.visible_len()?;
+                    if ansi::visible_width_from(self.line_len, &self.space) + word_len
+                        > self.options.limit()
+                        && word_len < self.options.limit()
+                    {
+                        self.add_newline();
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prepares a chunk of input according to [`WrapOptions::keep_newlines`]. If line breaks
+    /// aren't kept they are replaced with spaces, and the whole stream (rather than each chunk) is
+    /// trimmed.
+    fn prepare(&mut self, s: &str) -> String {
+        if self.options.keep_newlines() {
+            return s.to_string();
+        }
+
+        let started = self.trailing.is_some();
+        let mut input = self.trailing.take().unwrap_or_default();
+        input.extend(
+            s.chars()
+                .map(|ch| if self.options.is_newline(ch) { ' ' } else { ch }),
+        );
+        if !started {
+            input = input.trim_start().to_string();
+        }
+
+        let end = input.trim_end().len();
+        if started || end > 0 {
+            self.trailing = Some(input.split_off(end));
+        }
+        input
+    }
+
+    /// The line ending used for the output.
+    fn line_ending(&self) -> &'static str {
+        match self.options.line_endings() {
+            Some(line_ending) => line_ending.as_str(),
+            None if self.crlf => "\r\n",
+            None => DEFAULT_LINE_ENDING,
+        }
+    }
+
+    /// Whether whole paragraphs need to be buffered before they can be wrapped.
+    fn buffers_paragraphs(&self) -> bool {
+        self.options.break_strategy() == BreakStrategy::UnicodeLineBreak
+            || self.options.hyphenator().is_some()
+    }
+
+    fn push_word_char(&mut self, ch: char) {
+        self.word.push_str(ch.encode_utf8(&mut self.char_buffer));
+    }
+
+    fn add_space(&mut self) {
+        self.line_len = ansi::visible_width_from(self.line_len, &self.space);
+        self.buf.push_str(&self.space);
+        self.space.clear();
+    }
+
+    fn add_word(&mut self) -> Result<()> {
+        if !self.word.is_empty() {
+            self.add_space();
+            self.line_len += self.word.visible_len()?;
+            let word = self.word.to_str()?;
+            fragment::track_style(&mut self.style, word);
+            self.buf.push_str(word);
+            self.word.clear();
+            self.soft_hyphen = false;
+        }
+
+        Ok(())
+    }
+
+    fn add_paragraph(&mut self) {
+        if !self.paragraph.is_empty() {
+            let fragments = fragment::split(&self.paragraph, &self.options);
+            let line_ending = self.line_ending();
+            self.buf.push_str(&fragment::layout_greedy(
+                &fragments,
+                &self.options,
+                line_ending,
+                &mut self.style,
+            ));
+            self.paragraph.clear();
+        }
+    }
+
+    fn add_newline(&mut self) {
+        if self.soft_hyphen {
+            fragment::push_hyphen(&mut self.buf, self.options.hyphen(), &self.style);
+            self.soft_hyphen = false;
+        }
+        self.buf.push_str(self.line_ending());
+        self.line_len = 0;
+        self.space.clear();
+    }
+}
+
+impl Reflow for Writer {
+    fn write(&mut self, input: &str, out: &mut String) -> Result<()> {
+        self.write_str(input)?;
+        out.push_str(self.as_str());
+        self.inner.get_mut().clear();
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut String) -> Result<()> {
+        self.close()?;
+        out.push_str(self.as_str());
+        *self = Writer::with_options(self.options.clone());
+        Ok(())
+    }
+}
+
+impl<W: IoWrite> IoWrite for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.write_bytes(buf).map_err(Into::into)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
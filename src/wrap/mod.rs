@@ -0,0 +1,114 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+mod writer;
+pub use writer::{Writer, DEFAULT_TAB_WIDTH};
+
+use crate::Result;
+
+/// Hard-wraps the given string at `limit` columns, breaking lines in the middle of words if
+/// needed. See [`Writer`] for more options.
+///
+/// # Examples
+/// ```
+/// # use reflors::wrap::wrap_str;
+/// assert_eq!(wrap_str("Hello World!", 7).unwrap(), "Hello W\norld!");
+/// assert_eq!(wrap_str("\x1b[1mbold\x1b[0m text", 3).unwrap(), "\x1b[1mbol\nd\x1b[0m t\next");
+/// ```
+///
+/// ## Errors
+/// This function does not currently fail.
+pub fn wrap_str(input: &str, limit: usize) -> Result<String> {
+    let mut writer = Writer::new(limit);
+    writer.write_str(input)?;
+    writer.into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ansi;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn wrap() {
+        assert_eq!(wrap_str("foobarfoo", 4).unwrap(), "foob\narfo\no");
+        assert_eq!(wrap_str("foo bar", 3).unwrap(), "foo\nbar");
+        assert_eq!(wrap_str("foo\nbarfoo", 4).unwrap(), "foo\nbarf\noo");
+        assert_eq!(wrap_str("foo\r\nbarfoo", 4).unwrap(), "foo\r\nbarf\r\noo");
+        assert_eq!(wrap_str("\tfoo", 4).unwrap(), "    \nfoo");
+        assert_eq!(wrap_str("foobar", 0).unwrap(), "foobar");
+    }
+
+    #[test]
+    fn options() {
+        let mut writer = Writer::new(4).with_preserve_space(true);
+        writer.write_str("foo  bar").unwrap();
+        assert_eq!(writer.into_string().unwrap(), "foo \n bar");
+
+        let mut writer = Writer::new(4).with_keep_newlines(false).with_tab_width(2);
+        writer.write_str("foo\nbar\tb").unwrap();
+        assert_eq!(writer.into_string().unwrap(), "foob\nar  \nb");
+    }
+
+    #[test]
+    fn crlf() {
+        // the carriage return of a removed newline is removed as well, even if it is written
+        // separately
+        let mut writer = Writer::new(8).with_keep_newlines(false);
+        writer.write_str("ab\r\ncd\r").unwrap();
+        writer.write_str("\nef").unwrap();
+        assert_eq!(writer.into_string().unwrap(), "abcdef");
+
+        // other carriage returns are kept
+        let mut writer = Writer::new(8).with_keep_newlines(false);
+        writer.write_str("ab\rcd\r").unwrap();
+        assert_eq!(writer.into_string().unwrap(), "ab\rcd\r");
+        assert_eq!(wrap_str("ab\r\ncd", 8).unwrap(), "ab\r\ncd");
+    }
+
+    #[test]
+    fn tabs() {
+        // tabs expand to the next tab stop, like everywhere else
+        assert_eq!(wrap_str("ab\tc", 10).unwrap(), "ab      c");
+        assert_eq!(
+            ansi::visible_width(&wrap_str("ab\tc", 10).unwrap()),
+            ansi::visible_width("ab\tc")
+        );
+        assert_eq!(wrap_str("abcdefghij\tk", 12).unwrap(), "abcdefghij  \nk");
+        let mut writer = Writer::new(12).with_tab_width(4);
+        writer.write_str("ab\tc\td").unwrap();
+        assert_eq!(writer.into_string().unwrap(), "ab  c   d");
+    }
+
+    #[test]
+    fn wide() {
+        assert_eq!(wrap_str("東京タワー", 4).unwrap(), "東京\nタワ\nー");
+        assert_eq!(wrap_str("a東京タ", 4).unwrap(), "a東 \n京タ");
+        // a character wider than the limit still has to go somewhere
+        assert_eq!(wrap_str("東京", 1).unwrap(), "東\n京");
+
+        let mut writer = Writer::new(3).with_pad('.');
+        writer.write_str("\x1b[1m東京\x1b[0m").unwrap();
+        assert_eq!(writer.into_string().unwrap(), "\x1b[1m東.\n京\x1b[0m");
+
+        // characters split across writes are held back until they are complete
+        let text = "東京タワー";
+        for size in 1..text.len() {
+            let mut writer = Writer::new(4);
+            for chunk in text.as_bytes().chunks(size) {
+                assert_eq!(writer.write_bytes(chunk).unwrap(), chunk.len());
+            }
+            assert_eq!(writer.into_string().unwrap(), "東京\nタワ\nー");
+        }
+    }
+
+    #[test]
+    fn osc() {
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(wrap_str(link, 4).unwrap(), link);
+    }
+}
@@ -0,0 +1,281 @@
+// Copyright (c) 2022 Tony Barbitta
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::io::{Result as IoResult, Write as IoWrite};
+
+use crate::{
+    ansi,
+    fmt::Utf8Decoder,
+    iter::{EscapeBuffer, Token, Tokens},
+    pipeline::{self, Reflow},
+    Error, Result,
+};
+
+/// The default number of columns between tab stops, the same as every other width calculation
+/// uses (see [`ansi::TAB_WIDTH`]).
+pub const DEFAULT_TAB_WIDTH: usize = ansi::TAB_WIDTH;
+
+/// The default character filling the end of a line when a wide character didn't fit on it.
+pub const DEFAULT_PAD: char = ' ';
+
+/// A streaming, ansi-aware hard-wrapping writer. Unlike the [`wordwrap`](`crate::wordwrap`)
+/// writer it doesn't care about words: a line is broken as soon as the next character would
+/// exceed the limit.
+///
+/// By default tabs are expanded with spaces to the next tab stop (every [`DEFAULT_TAB_WIDTH`]
+/// columns), existing newlines are kept and whitespace at the start of a line that was broken by
+/// the writer is dropped. When newlines are removed, a carriage return right before one is removed
+/// as well.
+///
+/// A wide character that doesn't fit on a line is moved to the next one, and the column it leaves
+/// empty is filled with the [pad](`Writer::with_pad`) character so the line is exactly `limit`
+/// columns wide.
+///
+/// Source: [muesli/reflow/wrap/wrap.go](https://github.com/muesli/reflow/blob/master/wrap/wrap.go)
+#[allow(clippy::struct_excessive_bools)]
+pub struct Writer<W> {
+    limit: usize,
+    keep_newlines: bool,
+    preserve_space: bool,
+    tab_width: usize,
+    pad: char,
+    inner: W,
+    line_len: usize,
+    escapes: EscapeBuffer,
+    forced_newline: bool,
+    crlf: bool,
+    pending_cr: bool,
+    char_buffer: [u8; 4],
+    decoder: Utf8Decoder,
+}
+
+impl Writer<Vec<u8>> {
+    /// Creates a new [`Writer`] that wraps at `limit` using an internal buffer.
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        Self::new_piped(limit, Vec::new())
+    }
+
+    /// [Closes](`Writer::close`) this [`Writer`] and tries to create a string from its internal
+    /// buffer.
+    ///
+    /// ## Errors
+    /// - `crate::Error::Utf8` if the buffer is not a valid utf8 string
+    pub fn into_string(mut self) -> Result<String> {
+        self.close()?;
+        String::from_utf8(self.inner).map_err(Error::from)
+    }
+}
+
+impl<W> Writer<W> {
+    /// Creates a new [`Writer`] that wraps at `limit` and writes to `writer`.
+    #[must_use]
+    pub fn new_piped(limit: usize, writer: W) -> Self {
+        Self {
+            limit,
+            keep_newlines: true,
+            preserve_space: false,
+            tab_width: DEFAULT_TAB_WIDTH,
+            pad: DEFAULT_PAD,
+            inner: writer,
+            line_len: 0,
+            escapes: EscapeBuffer::default(),
+            forced_newline: false,
+            crlf: false,
+            pending_cr: false,
+            char_buffer: [0; 4],
+            decoder: Utf8Decoder::default(),
+        }
+    }
+
+    /// Sets whether newlines in the input are kept, or removed before wrapping.
+    #[must_use]
+    pub fn with_keep_newlines(mut self, keep_newlines: bool) -> Self {
+        self.keep_newlines = keep_newlines;
+        self
+    }
+
+    /// Sets whether whitespace at the start of a line that was broken by the writer is kept.
+    #[must_use]
+    pub fn with_preserve_space(mut self, preserve_space: bool) -> Self {
+        self.preserve_space = preserve_space;
+        self
+    }
+
+    /// Sets the number of columns between tab stops, tabs are expanded with spaces up to the next
+    /// one.
+    #[must_use]
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width;
+        self
+    }
+
+    /// Sets the character filling the end of a line when a wide character didn't fit on it.
+    #[must_use]
+    pub fn with_pad(mut self, pad: char) -> Self {
+        self.pad = pad;
+        self
+    }
+
+    /// Gets the width lines are wrapped at.
+    #[must_use]
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// **Consumes** this [`Writer`] and returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: IoWrite> Writer<W> {
+    /// Write the given bytes to this [`Writer`]. A utf8 character may be split across writes, its
+    /// start is held back until the rest of it is written.
+    ///
+    /// ## Errors
+    /// - `Error::Utf8` - If `bytes` can't be valid utf8
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
+        let s = self.decoder.decode(bytes)?;
+        self.write_str(&s)?;
+        Ok(bytes.len())
+    }
+
+    /// Write the given string to this [`Writer`].
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to the underlying writer fails
+    pub fn write_str(&mut self, s: &str) -> Result<usize> {
+        let input = self.escapes.complete(s);
+        for token in Tokens::new(&input) {
+            let text = match token {
+                Token::Text(text) => text,
+                Token::Escape { raw, .. } => {
+                    self.write_pending_cr()?;
+                    self.inner.write_all(raw.as_bytes())?;
+                    continue;
+                }
+            };
+
+            for ch in text.chars() {
+                match ch {
+                    '\n' if !self.keep_newlines => {
+                        // the carriage return of a removed newline is removed along with it
+                        self.pending_cr = false;
+                    }
+                    '\n' => {
+                        self.crlf |= self.pending_cr;
+                        self.write_pending_cr()?;
+                        self.line_len = 0;
+                        self.forced_newline = false;
+                        self.write_char(ch)?;
+                    }
+                    '\r' => {
+                        // held back until it is known whether a newline follows
+                        self.write_pending_cr()?;
+                        self.pending_cr = true;
+                    }
+                    '\t' => {
+                        self.write_pending_cr()?;
+                        if self.tab_width > 0 {
+                            let spaces = self.tab_width - self.line_len % self.tab_width;
+                            for _ in 0..spaces {
+                                self.write_visible(' ')?;
+                            }
+                        }
+                    }
+                    _ => {
+                        self.write_pending_cr()?;
+                        self.write_visible(ch)?;
+                    }
+                }
+            }
+        }
+
+        Ok(s.len())
+    }
+
+    /// Writes a carriage return or an escape sequence that is still being held back and flushes
+    /// the underlying writer.
+    ///
+    /// ## Errors
+    /// - `Error::Io` - If writing to or flushing the underlying writer fails
+    pub fn close(&mut self) -> Result<()> {
+        self.write_pending_cr()?;
+        let held = self.escapes.take();
+        self.inner.write_all(held.as_bytes())?;
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    /// Writes a visible character, breaking the line before it if it doesn't fit. Whitespace at
+    /// the start of a line that was broken here is dropped, unless spaces are preserved.
+    fn write_visible(&mut self, ch: char) -> Result<()> {
+        let width = ansi::char_width(ch);
+        if self.limit > 0 && self.line_len > 0 && self.line_len + width > self.limit {
+            for _ in self.line_len..self.limit {
+                self.write_char(self.pad)?;
+            }
+            let line_ending = if self.crlf { "\r\n" } else { "\n" };
+            self.inner.write_all(line_ending.as_bytes())?;
+            self.line_len = 0;
+            self.forced_newline = true;
+        }
+
+        if self.line_len == 0 {
+            if self.forced_newline && !self.preserve_space && ch.is_whitespace() {
+                return Ok(());
+            }
+        } else {
+            self.forced_newline = false;
+        }
+
+        self.line_len += width;
+        self.write_char(ch)
+    }
+
+    fn write_pending_cr(&mut self) -> Result<()> {
+        if std::mem::take(&mut self.pending_cr) {
+            self.write_char('\r')?;
+        }
+        Ok(())
+    }
+
+    fn write_char(&mut self, ch: char) -> Result<()> {
+        self.inner
+            .write_all(ch.encode_utf8(&mut self.char_buffer).as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Reflow for Writer<Vec<u8>> {
+    fn write(&mut self, input: &str, out: &mut String) -> Result<()> {
+        self.write_str(input)?;
+        pipeline::drain(&mut self.inner, out)
+    }
+
+    fn finish(&mut self, out: &mut String) -> Result<()> {
+        self.close()?;
+        pipeline::drain(&mut self.inner, out)?;
+        *self = Writer::new(self.limit)
+            .with_keep_newlines(self.keep_newlines)
+            .with_preserve_space(self.preserve_space)
+            .with_tab_width(self.tab_width)
+            .with_pad(self.pad);
+        Ok(())
+    }
+}
+
+impl<W: IoWrite> IoWrite for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.write_bytes(buf).map_err(Into::into)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}